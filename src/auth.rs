@@ -0,0 +1,112 @@
+use reqwest::cookie::Jar;
+use reqwest::Url;
+use std::env;
+use std::fs;
+use std::io;
+
+/// Secrets are never written in options.cfg itself, only the name of an environment
+/// variable holding them, e.g. `$MAPPER_PASSWORD`.
+pub fn secret_from_env(key: &str, value: &str) -> Result<String, String> {
+    let name = match value.strip_prefix('$') {
+        Some(name) => name,
+        None => {
+            return Err(format!(
+                "Option '{}' must name an environment variable like $MAPPER_SECRET, secrets are not read from options.cfg.",
+                key
+            ));
+        }
+    };
+    env::var(name).map_err(|_| format!("Environment variable {} used by option '{}' is not set.", name, key))
+}
+
+/// Login form fields which hold a password or token by their name, e.g. `password`,
+/// `user[pass]` or `api_token`. Their values have to come from the environment.
+pub fn is_secret_field(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["pass", "pwd", "secret", "token"].iter().any(|part| name.contains(part))
+}
+
+/// Imports a Netscape cookies.txt file (as exported by browsers and curl) into the jar.
+/// Returns the number of imported cookies.
+pub fn load_cookies_file(jar: &Jar, path: &str) -> io::Result<usize> {
+    let content = fs::read_to_string(path)?;
+    let mut count = 0;
+    for line in content.lines() {
+        let line = match line.strip_prefix("#HttpOnly_") {
+            Some(line) => line,
+            None => line,
+        };
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 7 {
+            continue;
+        }
+        let (domain, path, secure, name, value) = (fields[0], fields[2], fields[3], fields[5], fields[6]);
+        let host = domain.trim_start_matches('.');
+        let scheme = if secure.eq_ignore_ascii_case("TRUE") { "https" } else { "http" };
+        let url = match Url::parse(&format!("{}://{}{}", scheme, host, path)) {
+            Ok(url) => url,
+            Err(_) => {
+                continue;
+            }
+        };
+        let mut cookie = format!("{}={}; Path={}", name, value, path);
+        if domain.starts_with('.') {
+            cookie.push_str(&format!("; Domain={}", host));
+        }
+        if scheme == "https" {
+            cookie.push_str("; Secure");
+        }
+        jar.add_cookie_str(&cookie, &url);
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::cookie::CookieStore;
+
+    #[test]
+    fn secrets_come_from_the_environment() {
+        env::set_var("XML_SITEMAPPER_TEST_SECRET", "hunter2");
+        assert_eq!(secret_from_env("password", "$XML_SITEMAPPER_TEST_SECRET"), Ok(String::from("hunter2")));
+        assert!(secret_from_env("password", "hunter2").is_err());
+        assert!(secret_from_env("password", "$XML_SITEMAPPER_TEST_UNSET").is_err());
+    }
+
+    #[test]
+    fn secret_fields() {
+        assert!(is_secret_field("Password"));
+        assert!(is_secret_field("user[pwd]"));
+        assert!(is_secret_field("api_token"));
+        assert!(!is_secret_field("username"));
+    }
+
+    #[test]
+    fn cookies_file() {
+        let path = env::temp_dir().join(format!("xml_sitemapper_cookies_{}.txt", std::process::id()));
+        fs::write(
+            &path,
+            "# Netscape HTTP Cookie File\n\
+             .foo.bar\tTRUE\t/\tTRUE\t0\tsession\tabc\n\
+             #HttpOnly_foo.bar\tFALSE\t/admin\tFALSE\t0\tadmin\txyz\n\
+             broken line\n",
+        )
+        .unwrap();
+        let jar = Jar::default();
+        assert_eq!(load_cookies_file(&jar, path.to_str().unwrap()).unwrap(), 2);
+        fs::remove_file(&path).unwrap();
+        let cookies = |url: &str| {
+            jar.cookies(&Url::parse(url).unwrap())
+                .map(|h| String::from(h.to_str().unwrap()))
+                .unwrap_or_default()
+        };
+        assert_eq!(cookies("https://www.foo.bar/"), "session=abc");
+        assert_eq!(cookies("http://www.foo.bar/"), "");
+        assert_eq!(cookies("http://foo.bar/admin/"), "admin=xyz");
+    }
+}
@@ -0,0 +1,146 @@
+use crate::options::Options;
+use reqwest::blocking::Response;
+use reqwest::Url;
+
+const EXTENSIONS: [(&str, &str); 30] = [
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("php", "text/html"),
+    ("txt", "text/plain"),
+    ("xml", "application/xml"),
+    ("css", "text/css"),
+    ("js", "application/javascript"),
+    ("json", "application/json"),
+    ("pdf", "application/pdf"),
+    ("doc", "application/msword"),
+    ("docx", "application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+    ("xls", "application/vnd.ms-excel"),
+    ("xlsx", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("rar", "application/vnd.rar"),
+    ("7z", "application/x-7z-compressed"),
+    ("exe", "application/octet-stream"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+];
+
+/// Guesses the MIME type from the extension of the last path segment.
+pub fn guess(url: &Url) -> Option<&'static str> {
+    let last = url.path_segments()?.next_back()?;
+    let (_, ext) = last.rsplit_once('.')?;
+    let ext = ext.to_lowercase();
+    EXTENSIONS
+        .iter()
+        .find(|(e, _)| *e == ext)
+        .map(|(_, mime)| *mime)
+}
+
+/// MIME type of the response without parameters such as charset, empty if there is none.
+pub fn mime_of(response: &Response) -> String {
+    response
+        .headers()
+        .get("Content-Type")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.split(';').next())
+        .map(|h| h.trim().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Patterns are full types (`application/pdf`), type wildcards (`image/*`) or `*` for anything.
+fn matches(patterns: &[String], mime: &str) -> bool {
+    patterns.iter().any(|p| {
+        if p == "*" {
+            return true;
+        }
+        match p.strip_suffix("/*") {
+            Some(prefix) => mime.split('/').next() == Some(prefix),
+            None => p == mime,
+        }
+    })
+}
+
+/// Decides what to do with URLs based on their content type.
+pub struct ContentPolicy {
+    include: Vec<String>,
+    head_only: Vec<String>,
+    skip: Vec<String>,
+}
+
+impl ContentPolicy {
+    pub fn new(options: &Options) -> ContentPolicy {
+        let lower = |v: &Vec<String>| v.iter().map(|p| p.to_lowercase()).collect();
+        ContentPolicy {
+            include: lower(&options.include_types),
+            head_only: lower(&options.head_only_types),
+            skip: lower(&options.skip_types),
+        }
+    }
+
+    pub fn includes(&self, mime: &str) -> bool {
+        matches(&self.include, mime) && !self.skips(mime)
+    }
+
+    pub fn head_only(&self, mime: &str) -> bool {
+        matches(&self.head_only, mime)
+    }
+
+    pub fn skips(&self, mime: &str) -> bool {
+        matches(&self.skip, mime)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guess_of(url: &str) -> Option<&'static str> {
+        guess(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn guess_from_the_last_segment() {
+        assert_eq!(guess_of("https://foo.bar/docs/Report.PDF"), Some("application/pdf"));
+        assert_eq!(guess_of("https://foo.bar/a.tar.gz?x=1"), Some("application/gzip"));
+        assert_eq!(guess_of("https://foo.bar/v1.2/page"), None);
+        assert_eq!(guess_of("https://foo.bar/file.unknown"), None);
+        assert_eq!(guess_of("https://foo.bar/"), None);
+    }
+
+    #[test]
+    fn patterns() {
+        let patterns = vec![String::from("image/*"), String::from("application/pdf")];
+        assert!(matches(&patterns, "image/png"));
+        assert!(matches(&patterns, "application/pdf"));
+        assert!(!matches(&patterns, "application/pdf-x"));
+        assert!(!matches(&patterns, "imagery/png"));
+        assert!(matches(&[String::from("*")], "text/html"));
+        assert!(!matches(&[], "text/html"));
+    }
+
+    #[test]
+    fn skip_wins_over_include() {
+        let mut options = Options::default();
+        options.set("include_types", "text/*, Application/PDF").unwrap();
+        options.set("head_only_types", "application/pdf").unwrap();
+        options.set("skip_types", "text/css").unwrap();
+        let policy = ContentPolicy::new(&options);
+        assert!(policy.includes("text/html"));
+        assert!(policy.includes("application/pdf"));
+        assert!(!policy.includes("text/css"));
+        assert!(!policy.includes("image/png"));
+        assert!(policy.head_only("application/pdf"));
+        assert!(!policy.head_only("text/html"));
+        assert!(policy.skips("text/css"));
+    }
+}
@@ -0,0 +1,72 @@
+use flate2::read::ZlibDecoder;
+use regex::bytes::Regex as BytesRegex;
+use regex::Regex;
+use std::io::Read;
+use std::sync::OnceLock;
+
+const URL_PATTERN: &str = r#"https?://[^\s<>"'()\[\]{}\\]+"#;
+
+// Compiled once, the crawl runs these on every document.
+static URL: OnceLock<Regex> = OnceLock::new();
+static PDF_URI: OnceLock<BytesRegex> = OnceLock::new();
+static PDF_STREAM: OnceLock<BytesRegex> = OnceLock::new();
+
+/// Absolute URLs written anywhere in the text, e.g. in a plain text or XML document.
+pub fn from_text(text: &str) -> Vec<String> {
+    let re = URL.get_or_init(|| Regex::new(URL_PATTERN).unwrap());
+    re.find_iter(text)
+        .map(|m| String::from(m.as_str().trim_end_matches(['.', ',', ';', ':'])))
+        .collect()
+}
+
+fn scan_pdf_chunk(chunk: &[u8], links: &mut Vec<String>) {
+    let uri = PDF_URI.get_or_init(|| BytesRegex::new(r"(?-u)/URI\s*\(((?:[^()\\]|\\.)*)\)").unwrap());
+    for cap in uri.captures_iter(chunk) {
+        let raw = String::from_utf8_lossy(&cap[1]).replace("\\(", "(").replace("\\)", ")").replace("\\\\", "\\");
+        links.push(raw);
+    }
+    // Annotations are taken out first, escaped characters would cut their URLs short.
+    let rest = uri.replace_all(chunk, &b""[..]);
+    links.extend(from_text(&String::from_utf8_lossy(&rest)));
+}
+
+/// Link annotations (`/URI (...)`) and URLs in the text of a PDF file. Only uncompressed and
+/// FlateDecode streams are looked into, which covers the output of common PDF producers.
+pub fn from_pdf(bytes: &[u8]) -> Vec<String> {
+    let mut links = Vec::new();
+    scan_pdf_chunk(bytes, &mut links);
+    let stream = PDF_STREAM.get_or_init(|| BytesRegex::new(r"(?s-u)stream\r?\n(.*?)\r?\nendstream").unwrap());
+    for cap in stream.captures_iter(bytes) {
+        let mut decoded = Vec::new();
+        if ZlibDecoder::new(&cap[1]).read_to_end(&mut decoded).is_ok() {
+            scan_pdf_chunk(&decoded, &mut links);
+        }
+    }
+    links.sort();
+    links.dedup();
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    #[test]
+    fn urls_in_text() {
+        let text = "See https://foo.bar/a, (http://foo.bar/b) and <https://foo.bar/c?x=1>. Not ftp://foo.bar/d.";
+        assert_eq!(from_text(text), vec!["https://foo.bar/a", "http://foo.bar/b", "https://foo.bar/c?x=1"]);
+    }
+
+    #[test]
+    fn pdf_annotations_and_streams() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"BT (Visit https://foo.bar/compressed) Tj ET").unwrap();
+        let mut pdf = Vec::from(&b"%PDF-1.4\n<< /A << /S /URI /URI (https://foo.bar/a\\(1\\)) >> >>\nstream\n"[..]);
+        pdf.extend(encoder.finish().unwrap());
+        pdf.extend(b"\nendstream\n<< /URI (https://foo.bar/a\\(1\\)) >>\n");
+        assert_eq!(from_pdf(&pdf), vec!["https://foo.bar/a(1)", "https://foo.bar/compressed"]);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::options::{DedupeMode, DedupePreference};
+use crate::output;
+use reqwest::Url;
+use select::document::Document;
+use select::node::Node;
+use select::predicate::Name;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+
+/// Hashes of the visible text of a page, whitespace collapsed.
+pub struct Fingerprint {
+    pub url: Url,
+    pub exact: u64,
+    pub simhash: u64,
+}
+
+fn hash<T: Hash>(value: T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Elements whose text is not shown to visitors, usually shared template code.
+const HIDDEN: [&str; 4] = ["script", "style", "noscript", "template"];
+
+fn collect_text(node: Node, out: &mut String) {
+    if node.name().is_some_and(|name| HIDDEN.contains(&name)) {
+        return;
+    }
+    if let Some(text) = node.as_text() {
+        out.push_str(text);
+    }
+    for child in node.children() {
+        collect_text(child, out);
+    }
+}
+
+/// Text of the page body (or the whole document without one), leaving out scripts and styles.
+pub fn visible_text(html: &Document) -> String {
+    let mut text = String::new();
+    match html.find(Name("body")).next() {
+        Some(body) => collect_text(body, &mut text),
+        None => {
+            for node in html.find(Name("html")) {
+                collect_text(node, &mut text);
+            }
+        }
+    }
+    text
+}
+
+impl Fingerprint {
+    /// Returns `None` for pages with fewer than `min_words` words: empty pages, image-only pages
+    /// and redirect stubs all hash alike and would otherwise end up in one duplicate group.
+    pub fn new(url: Url, text: &str, min_words: usize) -> Option<Fingerprint> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if words.is_empty() || words.len() < min_words {
+            return None;
+        }
+        let mut weights = [0i64; 64];
+        // Three word shingles, so reordered paragraphs still differ from each other.
+        for shingle in words.windows(3.min(words.len().max(1))) {
+            let h = hash(shingle);
+            for (bit, weight) in weights.iter_mut().enumerate() {
+                if h & (1 << bit) != 0 {
+                    *weight += 1;
+                } else {
+                    *weight -= 1;
+                }
+            }
+        }
+        let simhash = weights
+            .iter()
+            .enumerate()
+            .filter(|(_, w)| **w > 0)
+            .fold(0u64, |acc, (bit, _)| acc | (1 << bit));
+        Some(Fingerprint {
+            url,
+            exact: hash(words),
+            simhash,
+        })
+    }
+}
+
+pub struct DuplicateGroup {
+    pub kept: Url,
+    pub dropped: Vec<Url>,
+}
+
+fn find(parent: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    parent[i] = root;
+    root
+}
+
+/// Groups pages with identical text, and with `Similar` also pages whose SimHash differs in
+/// at most `threshold` bits. Near-duplicate search compares every pair of pages.
+pub fn group(fingerprints: &[Fingerprint], mode: DedupeMode, threshold: u32) -> Vec<Vec<usize>> {
+    let n = fingerprints.len();
+    let mut parent: Vec<usize> = (0..n).collect();
+    let mut by_hash: HashMap<u64, usize> = HashMap::new();
+    for (i, fp) in fingerprints.iter().enumerate() {
+        match by_hash.get(&fp.exact) {
+            Some(&j) => {
+                let root = find(&mut parent, j);
+                parent[i] = root;
+            }
+            None => {
+                by_hash.insert(fp.exact, i);
+            }
+        }
+    }
+    if mode == DedupeMode::Similar {
+        for i in 0..n {
+            for j in (i + 1)..n {
+                if (fingerprints[i].simhash ^ fingerprints[j].simhash).count_ones() <= threshold {
+                    let a = find(&mut parent, i);
+                    let b = find(&mut parent, j);
+                    if a != b {
+                        parent[a.max(b)] = a.min(b);
+                    }
+                }
+            }
+        }
+    }
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..n {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(i);
+    }
+    let mut groups: Vec<Vec<usize>> = groups.into_values().filter(|g| g.len() > 1).collect();
+    groups.sort();
+    groups
+}
+
+/// Picks the page to keep; indices are in crawl order, so the first one was crawled first.
+pub fn representative(
+    fingerprints: &[Fingerprint],
+    group: &[usize],
+    prefer: DedupePreference,
+    priorities: &HashMap<Url, f64>,
+) -> usize {
+    let url = |i: &usize| &fingerprints[*i].url;
+    let chosen = match prefer {
+        DedupePreference::FirstCrawled => group.iter().min(),
+        DedupePreference::Shortest => group
+            .iter()
+            .min_by_key(|i| (url(i).as_str().len(), **i)),
+        DedupePreference::FewestParams => group
+            .iter()
+            .min_by_key(|i| (url(i).query_pairs().count(), url(i).as_str().len(), **i)),
+        DedupePreference::HighestPriority => group.iter().max_by(|a, b| {
+            let pa = priorities.get(url(a)).unwrap_or(&0.0);
+            let pb = priorities.get(url(b)).unwrap_or(&0.0);
+            pa.partial_cmp(pb)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.cmp(a))
+        }),
+    };
+    *chosen.unwrap_or(&group[0])
+}
+
+pub fn write_report(groups: &[DuplicateGroup], path: &str) -> io::Result<()> {
+    output::write_atomic(path, |out| {
+        writeln!(out, "# Groups of pages with duplicate content ({})", groups.len())?;
+        for group in groups.iter() {
+            writeln!(out, "\nkept: {}", group.kept)?;
+            for url in group.dropped.iter() {
+                writeln!(out, "dropped: {}", url)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "the quick brown fox jumps over the lazy dog while the cat sleeps in the warm sun all day long";
+
+    fn fingerprint(url: &str, text: &str) -> Fingerprint {
+        Fingerprint::new(Url::parse(url).unwrap(), text, 5).unwrap()
+    }
+
+    #[test]
+    fn visible_text_leaves_out_scripts_and_styles() {
+        let html = Document::from("<html><head><title>T</title><style>p {}</style></head><body><p>Hello <b>world</b></p><script>var x;</script><noscript>on</noscript></body></html>");
+        assert_eq!(visible_text(&html).split_whitespace().collect::<Vec<_>>(), vec!["Hello", "world"]);
+    }
+
+    #[test]
+    fn short_pages_get_no_fingerprint() {
+        let url = Url::parse("https://foo.bar/").unwrap();
+        assert!(Fingerprint::new(url.clone(), "", 0).is_none());
+        assert!(Fingerprint::new(url.clone(), "moved here", 5).is_none());
+        assert!(Fingerprint::new(url, TEXT, 5).is_some());
+    }
+
+    #[test]
+    fn exact_and_similar_groups() {
+        let fingerprints = vec![
+            fingerprint("https://foo.bar/a", TEXT),
+            fingerprint("https://foo.bar/b", &format!("  {}\n", TEXT.replace(' ', "\n"))),
+            fingerprint("https://foo.bar/c", &TEXT.replace("long", "longer")),
+            fingerprint("https://foo.bar/d", "something completely different with other words in it and no overlap at all here"),
+        ];
+        assert_eq!(group(&fingerprints, DedupeMode::Exact, 3), vec![vec![0, 1]]);
+        assert_eq!(group(&fingerprints, DedupeMode::Similar, 10), vec![vec![0, 1, 2]]);
+    }
+
+    #[test]
+    fn representative_by_preference() {
+        let fingerprints = vec![
+            fingerprint("https://foo.bar/page?a=1&b=2", TEXT),
+            fingerprint("https://foo.bar/a-much-longer-page-name", TEXT),
+            fingerprint("https://foo.bar/p?x=1", TEXT),
+        ];
+        let group = [0, 1, 2];
+        let mut priorities = HashMap::new();
+        priorities.insert(Url::parse("https://foo.bar/a-much-longer-page-name").unwrap(), 0.9);
+        let pick = |prefer| representative(&fingerprints, &group, prefer, &priorities);
+        assert_eq!(pick(DedupePreference::FirstCrawled), 0);
+        assert_eq!(pick(DedupePreference::Shortest), 2);
+        assert_eq!(pick(DedupePreference::FewestParams), 1);
+        assert_eq!(pick(DedupePreference::HighestPriority), 1);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::auth;
+use crate::options::Options;
+use crate::tls;
+use reqwest::blocking::{Client, ClientBuilder, RequestBuilder, Response};
+use reqwest::cookie::Jar;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Proxy;
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Builds the client shared by every request of the crawl.
+pub fn build(options: &Options) -> Result<Client, String> {
+    builder(options)?
+        .build()
+        .map_err(|e| format!("Unable to create the HTTP client: {}", e))
+}
+
+/// Client builder with every option of options.cfg applied.
+pub fn builder(options: &Options) -> Result<ClientBuilder, String> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in options.headers.iter() {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("Invalid header name '{}' in options.cfg.", name))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|_| format!("Invalid value of header '{}' in options.cfg.", name))?;
+        headers.append(name, value);
+    }
+    let mut builder = Client::builder()
+        .user_agent(options.user_agent.as_str())
+        .default_headers(headers)
+        .pool_max_idle_per_host(options.pool_max_idle);
+    if options.connect_timeout > 0 {
+        builder = builder.connect_timeout(Duration::from_secs(options.connect_timeout));
+    }
+    builder = if options.timeout > 0 {
+        builder.timeout(Duration::from_secs(options.timeout))
+    } else {
+        builder.timeout(None)
+    };
+    if let Some(proxy) = &options.proxy {
+        let proxy = Proxy::all(proxy.as_str())
+            .map_err(|e| format!("Invalid proxy '{}' in options.cfg: {}", proxy, e))?;
+        builder = builder.proxy(proxy);
+    }
+    if options.cookie_jar || options.cookies_file.is_some() || options.login_url.is_some() {
+        let jar = Jar::default();
+        if let Some(file) = &options.cookies_file {
+            auth::load_cookies_file(&jar, file)
+                .map_err(|e| format!("Unable to read cookies from '{}': {}", file, e))?;
+        }
+        builder = builder.cookie_provider(Arc::new(jar));
+    }
+    tls::configure(builder, options)
+}
+
+/// Adds the basic or bearer credentials of options.cfg to the request.
+pub fn authorize(request: RequestBuilder, options: &Options) -> RequestBuilder {
+    let mut request = request;
+    if let Some(user) = &options.basic_auth_user {
+        request = request.basic_auth(user, options.basic_auth_password.as_ref());
+    }
+    if let Some(token) = &options.bearer_token {
+        request = request.bearer_auth(token);
+    }
+    request
+}
+
+/// Reads the whole body, failing as soon as it turns out to be longer than `limit` bytes.
+pub fn read_limited(response: Response, limit: Option<u64>) -> io::Result<Vec<u8>> {
+    let limit = match limit {
+        Some(limit) => limit,
+        None => {
+            let mut body = Vec::new();
+            response.take(u64::MAX).read_to_end(&mut body)?;
+            return Ok(body);
+        }
+    };
+    if response.content_length().is_some_and(|len| len > limit) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("response is larger than {} bytes", limit),
+        ));
+    }
+    let mut body = Vec::new();
+    response.take(limit + 1).read_to_end(&mut body)?;
+    if body.len() as u64 > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("response is larger than {} bytes", limit),
+        ));
+    }
+    Ok(body)
+}
+
+/// Decodes the body with the charset from the Content-Type header, UTF-8 by default.
+pub fn decode_text(body: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(|ct| {
+            ct.split(';')
+                .filter_map(|p| p.trim().split_once('='))
+                .find(|(k, _)| k.trim().eq_ignore_ascii_case("charset"))
+                .map(|(_, v)| v.trim().trim_matches('"').to_string())
+        })
+        .and_then(|label| encoding_rs::Encoding::for_label(label.as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    let (text, _, _) = encoding.decode(body);
+    text.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_with_charset() {
+        assert_eq!(decode_text(b"caf\xe9", Some("text/html; Charset=\"ISO-8859-1\"")), "café");
+        assert_eq!(decode_text("café".as_bytes(), Some("text/html")), "café");
+        assert_eq!(decode_text("café".as_bytes(), Some("text/html; charset=nonsense")), "café");
+        assert_eq!(decode_text(b"caf\xe9", None), "caf\u{fffd}");
+    }
+
+    #[test]
+    fn invalid_client_options() {
+        let mut options = Options::default();
+        options.set("header", "X Bad: 1").unwrap();
+        assert_eq!(build(&options).err(), Some(String::from("Invalid header name 'X Bad' in options.cfg.")));
+        let mut options = Options::default();
+        options.set("proxy", "not a proxy").unwrap();
+        assert!(build(&options).unwrap_err().starts_with("Invalid proxy 'not a proxy' in options.cfg"));
+        let mut options = Options::default();
+        options.set("header", "X-Env: staging").unwrap();
+        options.set("timeout", "0").unwrap();
+        assert!(build(&options).is_ok());
+    }
+
+    #[test]
+    fn credentials() {
+        let client = Client::new();
+        let header = |options: &Options| {
+            let request = authorize(client.get("https://foo.bar/"), options).build().unwrap();
+            request.headers().get("Authorization").map(|h| String::from(h.to_str().unwrap()))
+        };
+        assert_eq!(header(&Options::default()), None);
+        let mut options = Options::default();
+        options.set("basic_auth_user", "alice").unwrap();
+        options.basic_auth_password = Some(String::from("secret"));
+        assert_eq!(header(&options).as_deref(), Some("Basic YWxpY2U6c2VjcmV0"));
+        let options = Options { bearer_token: Some(String::from("abc")), ..Options::default() };
+        assert_eq!(header(&options).as_deref(), Some("Bearer abc"));
+    }
+}
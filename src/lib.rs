@@ -0,0 +1,990 @@
+//! The crawler behind the XmlSiteMapper-rs executable. Custom priorities can be plugged in by
+//! implementing `priority::PriorityStrategy` and passing it to `Mapper::set_priority_strategy`.
+
+use chrono::SecondsFormat;
+use regex::Regex;
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode, Url};
+use select::document::Document;
+use select::predicate::{Attr, Name};
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+use std::{collections::HashMap, collections::HashSet, collections::VecDeque, thread};
+
+pub mod terminal_writer;
+use terminal_writer::TermWriter;
+
+mod xml_file_writer;
+
+pub mod output;
+
+pub mod options;
+use options::{DedupeMode, DocumentKind, LinkSource, Options, PriorityKind};
+
+pub mod logger;
+use logger::{FetchEvent, Logger};
+
+pub mod url_list;
+
+mod scope;
+use scope::Scope;
+
+mod normalize;
+use normalize::Normalizer;
+
+mod content_type;
+use content_type::ContentPolicy;
+
+mod robots;
+use robots::Robots;
+
+mod doc_links;
+
+pub mod duplicates;
+use duplicates::{DuplicateGroup, Fingerprint};
+
+mod auth;
+
+pub mod http_client;
+
+mod tls;
+
+mod retry;
+
+pub mod rewrite;
+
+pub mod link_graph;
+use link_graph::LinkGraph;
+
+pub mod orphans;
+use orphans::OrphanReport;
+
+pub mod stats;
+use stats::CrawlStats;
+
+pub mod metrics;
+use metrics::SharedMetrics;
+
+pub mod sitemap_diff;
+
+pub mod validate;
+
+pub mod priority;
+use priority::{Inlinks, PageInfo, PageRank, PriorityChain, PriorityStrategy, RegexAdjust, SegmentCount};
+
+/// Extracts the URL from a `<meta http-equiv="refresh" content="5; url=/next">` value.
+fn meta_refresh_target(content: &str) -> Option<String> {
+    let (_, target) = content.split_once(';')?;
+    let target = target.trim();
+    let (key, value) = target.split_once('=')?;
+    if !key.trim().eq_ignore_ascii_case("url") {
+        return None;
+    }
+    let value = value.trim().trim_matches(|c| c == '\'' || c == '"').trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(String::from(value))
+    }
+}
+
+pub struct Mapper {
+    main_url: Url,
+    disallowed_extensions: HashSet<String>,
+    change_prio: HashMap<String, f64>,
+    delay: u64,
+    client: Client,
+    throttle: Cell<u64>,
+    term: TermWriter,
+    options: Options,
+    graph: LinkGraph,
+    scope: Scope,
+    normalizer: Normalizer,
+    content_policy: ContentPolicy,
+    fingerprints: Vec<Fingerprint>,
+    duplicates: Vec<DuplicateGroup>,
+    priority: Option<Box<dyn PriorityStrategy>>,
+    stats: CrawlStats,
+    metrics: SharedMetrics,
+    discovered: HashMap<Url, usize>,
+    last_modified: HashMap<Url, String>,
+    statuses: HashMap<Url, u16>,
+    robots: HashMap<String, Robots>,
+    /// Every url the crawl queued, whether it ended up in the sitemap or not.
+    visited: HashSet<Url>,
+}
+
+impl Mapper {
+    pub fn new(main_url: Url, disallowed_extensions: HashSet<String>, change_prio: HashMap<String, f64>, delay: u64, client: Client, terminal: TermWriter, options: Options) -> Mapper {
+        let scope = Scope::new(&main_url, &options);
+        let normalizer = Normalizer::new(&main_url, &options);
+        let content_policy = ContentPolicy::new(&options);
+        Mapper{main_url, disallowed_extensions, change_prio, delay, client, throttle: Cell::new(0), term: terminal, options, graph: LinkGraph::new(), scope, normalizer, content_policy, fingerprints: Vec::new(), duplicates: Vec::new(), priority: None, stats: CrawlStats::new(), metrics: SharedMetrics::new(), discovered: HashMap::new(), last_modified: HashMap::new(), statuses: HashMap::new(), robots: HashMap::new(), visited: HashSet::new()}
+    }
+
+    pub fn link_graph(&self) -> &LinkGraph {
+        &self.graph
+    }
+
+    fn start_logging(&mut self, links: i64, map_len: usize, log: &mut Logger) {
+        self.term.start_progress(links, map_len);
+        log.info(&format!("Crawling start: {} urls queued", links));
+    }
+
+    fn log_progress(&mut self, links: i64, map_len: usize, log: &mut Logger) {
+        log.debug(&format!("Size of queue on this iteration: {}", links));
+        self.metrics.update(|m| m.queue_size = links);
+        self.term.print_progress(links, map_len);
+    }
+
+    fn normalize_url(&self, url: Url) -> Option<Url> {
+        self.normalizer.normalize(url)
+    }
+
+    fn default_priority_strategy(&self, log: &mut Logger) -> PriorityChain {
+        let mut rules = Vec::new();
+        for i in self.change_prio.iter() {
+            match Regex::new(i.0) {
+                Ok(re) => {
+                    rules.push((re, *i.1));
+                }
+                Err(_) => {
+                    log.warn(&format!("Error while parsing a regex from change_prio.cfg: {}", i.0));
+                }
+            }
+        }
+        let base: Box<dyn PriorityStrategy> = match self.options.priority_strategy {
+            PriorityKind::Segments => Box::new(SegmentCount),
+            PriorityKind::Inlinks => Box::new(Inlinks::new()),
+            PriorityKind::PageRank => Box::new(PageRank::new()),
+        };
+        PriorityChain::new()
+            .then(base)
+            .then(Box::new(RegexAdjust::new(rules)))
+    }
+
+    pub fn set_priority_strategy(&mut self, strategy: Box<dyn PriorityStrategy>) {
+        self.priority = Some(strategy);
+    }
+
+    fn update_map(&mut self, pages: &mut HashMap<Url, PageInfo>, url: &Url, depth: usize, status: StatusCode, headers: &HeaderMap) {
+        if !self.discovered.contains_key(url) {
+            self.discovered.insert(url.clone(), self.discovered.len());
+        }
+        let last_modified = headers
+            .get("Last-Modified")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| chrono::DateTime::parse_from_rfc2822(h).ok());
+        if let Some(last_modified) = last_modified {
+            self.last_modified.insert(url.clone(), last_modified.to_rfc3339_opts(SecondsFormat::Secs, false));
+        }
+        if !pages.contains_key(url) {
+            let content_type = headers
+                .get("Content-Type")
+                .and_then(|h| h.to_str().ok())
+                .map(String::from);
+            let content_length = headers
+                .get("Content-Length")
+                .and_then(|h| h.to_str().ok())
+                .and_then(|h| h.parse().ok());
+            pages.insert(
+                url.clone(),
+                PageInfo {
+                    url: url.clone(),
+                    depth,
+                    inlinks: 0,
+                    status,
+                    content_type,
+                    content_length,
+                },
+            );
+        }
+    }
+
+    fn assign_priorities(&mut self, pages: &mut HashMap<Url, PageInfo>, map: &mut HashMap<Url, f64>, log: &mut Logger) {
+        let mut strategy = match self.priority.take() {
+            Some(strategy) => strategy,
+            None => Box::new(self.default_priority_strategy(log)),
+        };
+        let inlinks = {
+            let nodes: HashSet<&Url> = pages.keys().collect();
+            strategy.prepare(&self.graph, &nodes);
+            self.graph.inlink_counts(&nodes)
+        };
+        self.priority = Some(strategy);
+        for page in pages.values_mut() {
+            page.inlinks = inlinks[&page.url];
+            map.insert(page.url.clone(), self.page_priority(page));
+        }
+    }
+
+    fn remove_duplicates(&mut self, map: &mut HashMap<Url, f64>, log: &mut Logger) {
+        let groups = duplicates::group(&self.fingerprints, self.options.dedupe, self.options.dedupe_threshold);
+        for group in groups {
+            let kept = duplicates::representative(&self.fingerprints, &group, self.options.dedupe_prefer, map);
+            let dropped: Vec<Url> = group
+                .iter()
+                .filter(|i| **i != kept)
+                .map(|i| self.fingerprints[*i].url.clone())
+                .collect();
+            for url in dropped.iter() {
+                map.remove(url);
+                self.stats.exclude("duplicate", url.as_str());
+                log.info(&format!("Dropped '{}' as a duplicate of '{}'", url, self.fingerprints[kept].url));
+            }
+            self.duplicates.push(DuplicateGroup {
+                kept: self.fingerprints[kept].url.clone(),
+                dropped,
+            });
+        }
+    }
+
+    /// Whether the site root from site.cfg lies inside the crawl scope, e.g. inside scope_path_prefix.
+    pub fn root_in_scope(&self) -> bool {
+        let root = self.normalize_url(self.main_url.clone()).unwrap_or_else(|| self.main_url.clone());
+        self.scope.contains(&root)
+    }
+
+    /// Position of every page in the order the crawl reached it.
+    pub fn discovered(&self) -> &HashMap<Url, usize> {
+        &self.discovered
+    }
+
+    pub fn last_modified(&self) -> &HashMap<Url, String> {
+        &self.last_modified
+    }
+
+    /// Final status code of every fetched url, whether it made it into the sitemap or not.
+    pub fn statuses(&self) -> &HashMap<Url, u16> {
+        &self.statuses
+    }
+
+    pub fn metrics(&self) -> &SharedMetrics {
+        &self.metrics
+    }
+
+    pub fn stats(&self) -> &CrawlStats {
+        &self.stats
+    }
+
+    pub fn duplicates(&self) -> &[DuplicateGroup] {
+        &self.duplicates
+    }
+
+    fn page_priority(&self, page: &PageInfo) -> f64 {
+        let priority = match &self.priority {
+            Some(strategy) => strategy.priority(page, 1.0),
+            None => 1.0,
+        };
+        // sitemaps.org only allows 0.0..=1.0, change_prio.cfg rules can push the top pages above it.
+        priority.clamp(0.1, 1.0)
+    }
+
+    fn get_body(&self, url: &Url, log: &mut Logger) -> Option<reqwest::blocking::Response> {
+        self.request(url, Method::GET, log).ok()
+    }
+
+    /// Sends the request with retries. On failure returns the last status code, if any.
+    fn request(&self, url: &Url, method: Method, log: &mut Logger) -> Result<reqwest::blocking::Response, Option<StatusCode>> {
+        let mut attempt: u32 = 0;
+        let response = loop {
+            let response = self.authorize(self.client.request(method.clone(), url.clone()), url).send();
+            let wait = match &response {
+                Ok(res) if retry::is_retryable(res.status()) => {
+                    if retry::is_overload(res.status()) {
+                        self.slow_down(log);
+                    }
+                    match retry::retry_after(res) {
+                        Some(wait) if self.options.retry_after_max > 0 && wait > Duration::from_millis(self.options.retry_after_max) => {
+                            log.warn(&format!(
+                                "'{}' asks to retry after {} s, longer than retry_after_max, giving up.",
+                                url,
+                                wait.as_secs()
+                            ));
+                            None
+                        }
+                        Some(wait) => Some(wait),
+                        None => Some(retry::backoff(attempt, self.options.retry_base_delay, self.options.retry_max_delay)),
+                    }
+                }
+                Ok(_) => None,
+                Err(e) => {
+                    log.warn(&format!("Request to '{}' failed: {}", url, e));
+                    if e.is_connect() || e.is_timeout() || e.is_request() {
+                        Some(retry::backoff(attempt, self.options.retry_base_delay, self.options.retry_max_delay))
+                    } else {
+                        None
+                    }
+                }
+            };
+            match wait {
+                Some(wait) if attempt < self.options.retries => {
+                    attempt += 1;
+                    self.metrics.update(|m| m.retries += 1);
+                    let status = match &response {
+                        Ok(res) => res.status().to_string(),
+                        Err(_) => String::from("an error"),
+                    };
+                    log.info(&format!("Received {} from '{}', retrying in {} ms (attempt {} of {}).", status, url, wait.as_millis(), attempt, self.options.retries));
+                    thread::sleep(wait);
+                }
+                _ => break response,
+            }
+        };
+        let body = match response {
+            Ok(res) => res,
+            Err(e) => {
+                let class = if e.is_timeout() {
+                    "timeout"
+                } else if e.is_connect() {
+                    "connect"
+                } else {
+                    "request"
+                };
+                self.metrics.update(|m| *m.errors.entry(class).or_default() += 1);
+                return Err(None);
+            }
+        };
+        self.metrics.update(|m| *m.fetched.entry(body.status().as_u16()).or_default() += 1);
+        if body.status().is_success() {
+            self.speed_up();
+        }
+        match body.status() {
+            StatusCode::OK => {
+                log.debug(&format!("Successfully pinged '{}'.", url));
+                Ok(body)
+            }
+            s => {
+                let class = match s.as_u16() {
+                    300..=399 => "http_3xx",
+                    400..=499 => "http_4xx",
+                    500..=599 => "http_5xx",
+                    _ => "http_other",
+                };
+                self.metrics.update(|m| *m.errors.entry(class).or_default() += 1);
+                log.warn(&format!("Received {} status code from '{}', skipping...", s, url));
+                Err(Some(s))
+            }
+        }
+    }
+
+    /// Adds credentials from options.cfg, but only for URLs of the mapped site.
+    fn authorize(&self, request: RequestBuilder, url: &Url) -> RequestBuilder {
+        if !self.scope.contains(url) && url.origin() != self.main_url.origin() {
+            return request;
+        }
+        http_client::authorize(request, &self.options)
+    }
+
+    fn login(&self, log: &mut Logger) -> bool {
+        let login_url = match self.options.login_url.as_ref().and_then(|u| Url::parse(u).ok()) {
+            Some(login_url) => login_url,
+            None => {
+                return true;
+            }
+        };
+        let request = self.authorize(self.client.post(login_url.clone()).form(&self.options.login_fields), &login_url);
+        match request.send() {
+            Ok(res) if res.status().is_success() || res.status().is_redirection() => {
+                log.info(&format!("Logged in at '{}' with status {}.", login_url, res.status()));
+                true
+            }
+            Ok(res) => {
+                log.error(&format!("Login at '{}' failed with status {}.", login_url, res.status()));
+                false
+            }
+            Err(e) => {
+                log.error(&format!("Login at '{}' failed: {}", login_url, e));
+                false
+            }
+        }
+    }
+
+    fn read_body(&self, body: reqwest::blocking::Response, log: &mut Logger) -> Option<Vec<u8>> {
+        match http_client::read_limited(body, self.options.max_response_size) {
+            Ok(bytes) => {
+                self.metrics.update(|m| m.bytes += bytes.len() as u64);
+                Some(bytes)
+            }
+            Err(e) => {
+                self.metrics.update(|m| *m.errors.entry("body").or_default() += 1);
+                log.warn(&format!("Unable to read the body: {}", e));
+                None
+            }
+        }
+    }
+
+    /// Doubles the extra pause between requests after the server signalled overload.
+    fn slow_down(&self, log: &mut Logger) {
+        if !self.options.adaptive_delay {
+            return;
+        }
+        let throttle = (self.throttle.get() * 2).max(self.delay).max(100).min(self.options.retry_max_delay);
+        self.throttle.set(throttle);
+        log.warn(&format!("Server is overloaded, waiting {} ms more between requests.", throttle));
+    }
+
+    /// Shrinks the extra pause by a tenth after every successful response.
+    fn speed_up(&self) {
+        self.throttle.set(self.throttle.get() * 9 / 10);
+    }
+
+    fn pause(&self) {
+        thread::sleep(Duration::from_millis(self.delay + self.throttle.get()));
+    }
+
+    fn check_header(&self, body: &reqwest::blocking::Response) -> bool {
+        match body.headers().get("Content-Type") {
+            Some(url_check) => {
+                match url_check.to_str() {
+                    Ok(st) => {
+                        String::from(st).starts_with("text/html")
+                    }
+                    Err(_) => {
+                        false
+                    }
+                }
+            }
+            None => {
+                false
+            }
+        }
+    }
+
+    fn html_links(&mut self, url: &Url, html: &Document, included: bool) -> Vec<(String, String)> {
+        if included && self.options.dedupe != DedupeMode::Off {
+            let text = duplicates::visible_text(html);
+            if let Some(fingerprint) = Fingerprint::new(url.clone(), &text, self.options.dedupe_min_words) {
+                self.fingerprints.push(fingerprint);
+            }
+        }
+        let mut found = Vec::new();
+        for source in self.options.link_sources.iter() {
+            match source {
+                LinkSource::Anchor => {
+                    found.extend(html.find(Name("a")).filter_map(|h| h.attr("href")).map(|l| (String::from(l), String::from("<a href>"))));
+                }
+                LinkSource::Area => {
+                    found.extend(html.find(Name("area")).filter_map(|h| h.attr("href")).map(|l| (String::from(l), String::from("<area href>"))));
+                }
+                LinkSource::Link => {
+                    for node in html.find(Name("link")) {
+                        let rel = node.attr("rel").unwrap_or("").to_lowercase();
+                        let rel = rel.split_whitespace().find(|r| matches!(*r, "next" | "prev" | "alternate"));
+                        if let (Some(rel), Some(href)) = (rel, node.attr("href")) {
+                            found.push((String::from(href), format!("<link rel={}>", rel)));
+                        }
+                    }
+                }
+                LinkSource::Iframe => {
+                    found.extend(html.find(Name("iframe")).filter_map(|h| h.attr("src")).map(|l| (String::from(l), String::from("<iframe src>"))));
+                }
+                LinkSource::Form => {
+                    for node in html.find(Name("form")) {
+                        let method = node.attr("method").unwrap_or("get");
+                        if let (true, Some(action)) = (method.eq_ignore_ascii_case("get"), node.attr("action")) {
+                            found.push((String::from(action), String::from("<form action>")));
+                        }
+                    }
+                }
+                LinkSource::MetaRefresh => {
+                    for node in html.find(Name("meta")) {
+                        let refresh = node.attr("http-equiv").is_some_and(|h| h.eq_ignore_ascii_case("refresh"));
+                        if let (true, Some(content)) = (refresh, node.attr("content")) {
+                            if let Some(target) = meta_refresh_target(content) {
+                                found.push((target, String::from("<meta refresh>")));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        for attr in self.options.link_attributes.iter() {
+            found.extend(
+                html.find(Attr(attr.as_str(), ()))
+                    .filter_map(|h| h.attr(attr.as_str()))
+                    .map(|l| (String::from(l), format!("[{}]", attr))),
+            );
+        }
+        found
+    }
+
+    fn check_disallowed(&self, link: &str, log: &mut Logger) -> bool {
+        let mut flag = false;
+        for i in self.disallowed_extensions.iter() {
+            let re = Regex::new(i);
+            match re {
+                Ok(re) => {
+                    if re.is_match(link) {
+                        flag = true;
+                    }
+                }
+                Err(_) => {
+                    log.warn(&format!("Error while parsing a regex from disallow.cfg: {}", i));
+                }
+            }
+        }
+        flag
+    }
+
+    /// Checks the URL against the robots.txt of its origin, which is fetched on first use.
+    /// A robots.txt which cannot be read allows everything.
+    fn robots_allow(&mut self, url: &Url, log: &mut Logger) -> bool {
+        if !self.options.respect_robots {
+            return true;
+        }
+        let origin = url.origin().ascii_serialization();
+        if !self.robots.contains_key(&origin) {
+            let robots = match url.join("/robots.txt") {
+                Ok(location) => match self.authorize(self.client.get(location.clone()), &location).send() {
+                    Ok(res) if res.status().is_success() => match res.text() {
+                        Ok(text) => Robots::parse(&text, &self.options.user_agent),
+                        Err(_) => Robots::default(),
+                    },
+                    Ok(res) => {
+                        log.info(&format!("No robots.txt at '{}' (status {}).", location, res.status()));
+                        Robots::default()
+                    }
+                    Err(e) => {
+                        log.warn(&format!("Unable to read '{}': {}", location, e));
+                        Robots::default()
+                    }
+                },
+                Err(_) => Robots::default(),
+            };
+            self.robots.insert(origin.clone(), robots);
+        }
+        self.robots[&origin].allows(url)
+    }
+
+    fn fetch_text(&self, location: &str, log: &mut Logger) -> Option<String> {
+        let bytes = if location.starts_with("http://") || location.starts_with("https://") {
+            let url = Url::parse(location).ok()?;
+            let body = self.get_body(&url, log)?;
+            self.read_body(body, log)?
+        } else {
+            match std::fs::read(location) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    log.warn(&format!("Unable to read '{}': {}", location, e));
+                    return None;
+                }
+            }
+        };
+        match url_list::decode(&bytes) {
+            Ok(text) => Some(text),
+            Err(e) => {
+                log.warn(&format!("Unable to decode '{}': {}", location, e));
+                None
+            }
+        }
+    }
+
+    fn collect_seeds(&self, log: &mut Logger) -> Vec<String> {
+        let mut sitemaps: VecDeque<String> = self.options.seed_sitemaps.iter().cloned().collect();
+        if self.options.seed_robots {
+            if let Ok(robots) = self.main_url.join("/robots.txt") {
+                if let Some(text) = self.fetch_text(robots.as_str(), log) {
+                    sitemaps.extend(url_list::robots_sitemaps(&text));
+                }
+            }
+        }
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut seeds = Vec::new();
+        while let Some(location) = sitemaps.pop_front() {
+            if !seen.insert(location.clone()) {
+                continue;
+            }
+            log.info(&format!("Reading seed sitemap '{}'", location));
+            let text = match self.fetch_text(&location, log) {
+                Some(text) => text,
+                None => {
+                    continue;
+                }
+            };
+            match url_list::parse_sitemap(&text) {
+                Ok(sitemap) => {
+                    if sitemap.is_index {
+                        sitemaps.extend(sitemap.locs);
+                    } else {
+                        seeds.extend(sitemap.locs);
+                    }
+                }
+                Err(e) => {
+                    log.warn(&format!("Unable to parse sitemap '{}': {}", location, e));
+                }
+            }
+        }
+        if let Some(file) = &self.options.seed_urls_file {
+            match url_list::read_url_list(file) {
+                Ok(urls) => seeds.extend(urls),
+                Err(e) => {
+                    log.warn(&format!("Unable to read seed urls from '{}': {}", file, e));
+                }
+            }
+        }
+        seeds
+    }
+
+    fn scan_link(&mut self, map: &mut HashMap<Url, f64>, log: &mut Logger) {
+        let mut queue: VecDeque<(Url, usize, Option<Url>, Option<String>)> = VecDeque::new();
+        let mut pages: HashMap<Url, PageInfo> = HashMap::new();
+        let mut set: HashSet<Url> = HashSet::new();
+        let mut links: i64 = 1;
+        let mut graph = LinkGraph::new();
+        if !self.login(log) {
+            self.term.print_to_term(String::from("Login failed, crawling without a session."));
+        }
+        let root = self.normalize_url(self.main_url.clone()).unwrap_or_else(|| self.main_url.clone());
+        queue.push_front((root.clone(), 0, None, None));
+        set.insert(root.clone());
+        for seed in self.collect_seeds(log) {
+            if self.check_disallowed(&seed, log) {
+                self.stats.exclude("disallow", &seed);
+                continue;
+            }
+            let seed = match Url::parse(&seed).ok().and_then(|u| self.normalize_url(u)) {
+                Some(seed) => seed,
+                _ => {
+                    continue;
+                }
+            };
+            if !self.scope.contains(&seed) {
+                self.stats.exclude("scope", seed.as_str());
+            } else if !set.contains(&seed) {
+                links += 1;
+                set.insert(seed.clone());
+                queue.push_front((seed, 0, None, Some(String::from("seed"))));
+            }
+        }
+        self.start_logging(links, pages.len(), log);
+        while !queue.is_empty() {
+            self.log_progress(links, pages.len(), log);
+            self.pause();
+            let queue_pop = queue.pop_back();
+            links -= 1;
+            let mut url: Url;
+            let depth: usize;
+            let referrer: Option<Url>;
+            let source: Option<String>;
+            match queue_pop {
+                Some(queue_pop) => {
+                    url = queue_pop.0;
+                    depth = queue_pop.1;
+                    referrer = queue_pop.2;
+                    source = queue_pop.3;
+                }
+                None => {
+                    continue;
+                }
+            }
+            match self.normalize_url(url) {
+                Some(normalized) => {
+                    url = normalized;
+                },
+                None => {
+                    continue;
+                }
+            }
+            // The root is always crawled, staging servers usually disallow everything.
+            if url != root && !self.robots_allow(&url, log) {
+                log.debug(&format!("Skipping '{}', it is disallowed by robots.txt.", url));
+                self.stats.exclude("robots", url.as_str());
+                continue;
+            }
+            log.debug(&format!("Working with '{}' now", url.as_str()));
+            let mut event = FetchEvent {
+                url: &url,
+                status: None,
+                content_type: None,
+                bytes: None,
+                duration: Duration::from_millis(0),
+                depth,
+                referrer: referrer.as_ref(),
+                source: source.as_deref(),
+                decision: "skipped",
+            };
+            let guessed = content_type::guess(&url).unwrap_or("");
+            if self.content_policy.skips(guessed) {
+                log.debug(&format!("Skipping '{}' by its content type {}.", url, guessed));
+                log.fetch(&event);
+                self.stats.record(&event);
+                continue;
+            }
+            let head_only = self.content_policy.head_only(guessed);
+            let method = if head_only { Method::HEAD } else { Method::GET };
+            let started = Instant::now();
+            let body = match self.request(&url, method, log) {
+                Ok(result) => result,
+                Err(status) => {
+                    event.status = status.map(|s| s.as_u16());
+                    if let Some(status) = event.status {
+                        self.statuses.insert(url.clone(), status);
+                    }
+                    event.duration = started.elapsed();
+                    event.decision = "error";
+                    log.fetch(&event);
+                    self.stats.record(&event);
+                    continue;
+                }
+            };
+            let mime = content_type::mime_of(&body);
+            let mut included = self.content_policy.includes(&mime);
+            if !included {
+                log.debug(&format!("Content type '{}' is not included in the sitemap.", mime));
+                event.decision = "excluded";
+            } else if self.options.respect_noindex && robots::header_noindex(body.headers()) {
+                log.debug(&format!("'{}' is noindex by X-Robots-Tag.", url));
+                included = false;
+                event.decision = "noindex";
+            }
+            let status = body.status();
+            self.statuses.insert(url.clone(), status.as_u16());
+            let headers = body.headers().clone();
+            let content_type = body
+                .headers()
+                .get("Content-Type")
+                .and_then(|h| h.to_str().ok())
+                .map(String::from);
+            event.status = Some(body.status().as_u16());
+            event.content_type = content_type.as_deref();
+            event.bytes = body.content_length();
+            let is_html = self.check_header(&body);
+            let kind = DocumentKind::of_mime(&mime).filter(|k| self.options.document_links.contains(k));
+            let read = !head_only && (is_html || kind.is_some());
+            let bytes = if read { self.read_body(body, log) } else { None };
+            event.bytes = bytes.as_ref().map(|b| b.len() as u64).or(event.bytes);
+            let html = match &bytes {
+                Some(bytes) if is_html => Some(Document::from(http_client::decode_text(bytes, content_type.as_deref()).as_str())),
+                _ => None,
+            };
+            if included && self.options.respect_noindex && html.as_ref().is_some_and(robots::meta_noindex) {
+                log.debug(&format!("'{}' is noindex by <meta name=robots>.", url));
+                included = false;
+                event.decision = "noindex";
+            }
+            if included {
+                self.update_map(&mut pages, &url, depth, status, &headers);
+                graph.add_node(&url);
+                event.decision = "included";
+            }
+            event.duration = started.elapsed();
+            log.fetch(&event);
+            self.stats.record(&event);
+            if !read {
+                continue;
+            }
+            let found = if let Some(html) = &html {
+                self.html_links(&url, html, included)
+            } else if let Some(kind) = kind {
+                match bytes {
+                    Some(bytes) => {
+                        let text = || http_client::decode_text(&bytes, content_type.as_deref());
+                        let (links, source) = match kind {
+                            DocumentKind::Pdf => (doc_links::from_pdf(&bytes), "pdf"),
+                            DocumentKind::Text => (doc_links::from_text(&text()), "text"),
+                            DocumentKind::Xml => (doc_links::from_text(&text()), "xml"),
+                        };
+                        links.into_iter().map(|l| (l, String::from(source))).collect()
+                    }
+                    None => Vec::new(),
+                }
+            } else {
+                continue;
+            };
+            for (link, source) in found.iter() {
+                let flag = self.check_disallowed(link, log);
+                let link = match url.join(link) {
+                    Ok(link) => link,
+                    Err(_) => {
+                        continue;
+                    }
+                };
+                if !matches!(link.scheme(), "http" | "https") {
+                    continue;
+                }
+                if flag {
+                    self.stats.exclude("disallow", link.as_str());
+                    continue;
+                }
+                if !self.scope.contains(&link) {
+                    self.stats.exclude("scope", link.as_str());
+                    continue;
+                }
+                if let Some(link) = self.normalize_url(link) {
+                    graph.add_edge(&url, &link);
+                    if !set.contains(&link) {
+                        log.debug(&format!("Found '{}' via {}", link, source));
+                        links += 1;
+                        set.insert(link.clone());
+                        queue.push_front((link, depth + 1, Some(url.clone()), Some(source.clone())));
+                    }
+                }
+            }
+        }
+        self.graph = graph;
+        self.visited = set;
+        self.assign_priorities(&mut pages, map, log);
+        self.remove_duplicates(map, log);
+        log.info(&format!("Crawling end: {} pages crawled, building file sitemap.xml.", pages.len()));
+    }
+
+    pub fn generate_sitemap(&mut self, log: &mut Logger) -> HashMap<Url, f64> {
+        let mut result_map = HashMap::<Url, f64>::new();
+        let started = Instant::now();
+        self.scan_link(&mut result_map, log);
+        self.stats.duration = started.elapsed();
+        result_map
+    }
+
+    pub fn find_orphans(&self, known: &[String], map: &HashMap<Url, f64>) -> OrphanReport {
+        let mut orphans = Vec::new();
+        let mut ignored = Vec::new();
+        let mut known_set = HashSet::new();
+        for entry in known.iter() {
+            let url = match Url::parse(entry).ok().and_then(|u| self.normalize_url(u)) {
+                Some(url) => url,
+                None => {
+                    ignored.push(entry.clone());
+                    continue;
+                }
+            };
+            if !self.scope.contains(&url) {
+                ignored.push(entry.clone());
+                continue;
+            }
+            if !self.visited.contains(&url) && !known_set.contains(&url) {
+                orphans.push(url.clone());
+            }
+            known_set.insert(url);
+        }
+        let mut missing: Vec<Url> = map.keys().filter(|u| !known_set.contains(*u)).cloned().collect();
+        missing.sort();
+        OrphanReport { orphans, missing, merged: Vec::new(), ignored }
+    }
+
+    pub fn merge_orphans(&mut self, report: &mut OrphanReport, map: &mut HashMap<Url, f64>, log: &mut Logger) {
+        for url in report.orphans.iter() {
+            if self.check_disallowed(url.as_str(), log) {
+                self.stats.exclude("disallow", url.as_str());
+                log.info(&format!("Not merging orphan '{}', it is disallowed", url.as_str()));
+                continue;
+            }
+            if !self.robots_allow(url, log) {
+                self.stats.exclude("robots", url.as_str());
+                log.info(&format!("Not merging orphan '{}', it is disallowed by robots.txt", url.as_str()));
+                continue;
+            }
+            self.pause();
+            log.info(&format!("Verifying orphan '{}'", url.as_str()));
+            let body = match self.request(url, Method::GET, log) {
+                Ok(body) => body,
+                Err(status) => {
+                    if let Some(status) = status {
+                        self.statuses.insert(url.clone(), status.as_u16());
+                    }
+                    continue;
+                }
+            };
+            let status = body.status();
+            let headers = body.headers().clone();
+            self.statuses.insert(url.clone(), status.as_u16());
+            let mime = content_type::mime_of(&body);
+            if !self.content_policy.includes(&mime) {
+                self.stats.exclude("content type", url.as_str());
+                log.info(&format!("Not merging orphan '{}', its content type '{}' is not included", url.as_str(), mime));
+                continue;
+            }
+            if self.options.respect_noindex {
+                let noindex = robots::header_noindex(&headers)
+                    || (self.check_header(&body)
+                        && self.read_body(body, log).is_some_and(|bytes| {
+                            let content_type = headers.get("Content-Type").and_then(|h| h.to_str().ok());
+                            robots::meta_noindex(&Document::from(http_client::decode_text(&bytes, content_type).as_str()))
+                        }));
+                if noindex {
+                    self.stats.exclude("noindex", url.as_str());
+                    log.info(&format!("Not merging orphan '{}', it is noindex", url.as_str()));
+                    continue;
+                }
+            }
+            let mut pages = HashMap::new();
+            self.update_map(&mut pages, url, 0, status, &headers);
+            map.insert(url.clone(), self.page_priority(&pages[url]));
+            report.merged.push(url.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meta_refresh_targets() {
+        assert_eq!(meta_refresh_target("5; url=/next").as_deref(), Some("/next"));
+        assert_eq!(meta_refresh_target("0;URL='https://foo.bar/a'").as_deref(), Some("https://foo.bar/a"));
+        assert_eq!(meta_refresh_target("0; url = \" /b \"").as_deref(), Some("/b"));
+        assert_eq!(meta_refresh_target("30"), None);
+        assert_eq!(meta_refresh_target("0; url="), None);
+        assert_eq!(meta_refresh_target("0; target=/c"), None);
+    }
+
+    fn mapper(options: Options) -> Mapper {
+        let root = Url::parse("https://foo.bar/").unwrap();
+        Mapper::new(root, HashSet::new(), HashMap::new(), 0, Client::new(), TermWriter::new(false), options)
+    }
+
+    #[test]
+    fn links_by_source() {
+        let html = Document::from(
+            "<html><head><link rel=\"Next stylesheet\" href=\"/2\"><link rel=\"icon\" href=\"/i.ico\">\
+             <meta http-equiv=\"Refresh\" content=\"3; url=/moved\"></head><body>\
+             <a href=\"/a\">a</a><a name=\"x\">x</a><map><area href=\"/area\"></map><iframe src=\"/frame\"></iframe>\
+             <form action=\"/search\"></form><form method=\"post\" action=\"/login\"></form>\
+             <div data-href=\"/custom\"></div></body></html>",
+        );
+        let url = Url::parse("https://foo.bar/").unwrap();
+        let mut options = Options::default();
+        options.set("link_sources", "a, area, link, iframe, form, meta_refresh").unwrap();
+        options.set("link_attributes", "data-href").unwrap();
+        let links = mapper(options).html_links(&url, &html, false);
+        let links: Vec<(&str, &str)> = links.iter().map(|(l, s)| (l.as_str(), s.as_str())).collect();
+        for expected in [
+            ("/a", "<a href>"),
+            ("/area", "<area href>"),
+            ("/2", "<link rel=next>"),
+            ("/frame", "<iframe src>"),
+            ("/search", "<form action>"),
+            ("/moved", "<meta refresh>"),
+            ("/custom", "[data-href]"),
+        ]
+        .iter()
+        {
+            assert!(links.contains(expected), "{:?} not in {:?}", expected, links);
+        }
+        assert_eq!(links.len(), 7);
+
+        let mut options = Options::default();
+        options.set("link_sources", "a").unwrap();
+        let links = mapper(options).html_links(&url, &html, false);
+        assert_eq!(links, vec![(String::from("/a"), String::from("<a href>"))]);
+    }
+
+    #[test]
+    fn orphans_are_known_urls_the_crawl_never_queued() {
+        let mut mapper = mapper(Options::default());
+        let url = |path: &str| Url::parse("https://foo.bar/").unwrap().join(path).unwrap();
+        mapper.visited = vec![url("/"), url("/a"), url("/noindex")].into_iter().collect();
+        let map: HashMap<Url, f64> = vec![(url("/"), 1.0), (url("/a"), 0.9)].into_iter().collect();
+        let known: Vec<String> = ["https://foo.bar/a#top", "https://foo.bar/noindex", "https://foo.bar/old", "https://foo.bar/old", "https://other.host/", "not a url"]
+            .iter()
+            .map(|s| String::from(*s))
+            .collect();
+        let report = mapper.find_orphans(&known, &map);
+        assert_eq!(report.orphans, vec![url("/old")]);
+        assert_eq!(report.missing, vec![url("/")]);
+        assert_eq!(report.ignored, vec!["https://other.host/", "not a url"]);
+    }
+}
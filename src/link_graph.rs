@@ -0,0 +1,328 @@
+use reqwest::Url;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+use xml::writer::{EmitterConfig, XmlEvent};
+
+const DAMPING: f64 = 0.85;
+const PAGE_RANK_ITERATIONS: usize = 30;
+
+#[derive(Default)]
+pub struct LinkGraph {
+    nodes: HashSet<Url>,
+    edges: HashMap<Url, HashSet<Url>>,
+}
+
+/// Graph with nodes labelled by (possibly collapsed) URLs and edges weighted
+/// by the number of page links they stand for.
+type Collapsed = BTreeMap<String, BTreeMap<String, usize>>;
+
+impl LinkGraph {
+    pub fn new() -> LinkGraph {
+        LinkGraph::default()
+    }
+
+    pub fn add_node(&mut self, url: &Url) {
+        self.nodes.insert(url.clone());
+    }
+
+    pub fn add_edge(&mut self, from: &Url, to: &Url) {
+        if from == to {
+            return;
+        }
+        self.add_node(from);
+        self.add_node(to);
+        self.edges
+            .entry(from.clone())
+            .or_default()
+            .insert(to.clone());
+    }
+
+    /// Number of distinct pages from `nodes` linking to each of `nodes`.
+    pub fn inlink_counts(&self, nodes: &HashSet<&Url>) -> HashMap<Url, usize> {
+        let mut counts: HashMap<Url, usize> = nodes.iter().map(|u| ((*u).clone(), 0)).collect();
+        for (from, targets) in self.edges.iter() {
+            if !nodes.contains(from) {
+                continue;
+            }
+            for to in targets.iter() {
+                if let Some(count) = counts.get_mut(to) {
+                    *count += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    /// PageRank restricted to the subgraph induced by `nodes`. Pages without
+    /// outgoing links spread their rank evenly over the whole subgraph.
+    pub fn page_rank(&self, nodes: &HashSet<&Url>) -> HashMap<Url, f64> {
+        let n = nodes.len();
+        if n == 0 {
+            return HashMap::new();
+        }
+        let outgoing: HashMap<&Url, Vec<&Url>> = nodes
+            .iter()
+            .map(|u| {
+                let targets = match self.edges.get(*u) {
+                    Some(targets) => targets.iter().filter(|t| nodes.contains(t)).collect(),
+                    None => Vec::new(),
+                };
+                (*u, targets)
+            })
+            .collect();
+        let mut rank: HashMap<&Url, f64> = nodes.iter().map(|u| (*u, 1.0 / n as f64)).collect();
+        for _ in 0..PAGE_RANK_ITERATIONS {
+            let dangling: f64 = outgoing
+                .iter()
+                .filter(|(_, targets)| targets.is_empty())
+                .map(|(u, _)| rank[u])
+                .sum();
+            let base = (1.0 - DAMPING) / n as f64 + DAMPING * dangling / n as f64;
+            let mut next: HashMap<&Url, f64> = nodes.iter().map(|u| (*u, base)).collect();
+            for (from, targets) in outgoing.iter() {
+                if targets.is_empty() {
+                    continue;
+                }
+                let share = DAMPING * rank[from] / targets.len() as f64;
+                for to in targets.iter() {
+                    *next.get_mut(to).unwrap() += share;
+                }
+            }
+            rank = next;
+        }
+        rank.into_iter().map(|(u, r)| (u.clone(), r)).collect()
+    }
+}
+
+/// Linearly maps scores onto the 0.1..1.0 priority range.
+pub fn scale_to_priority(scores: HashMap<Url, f64>) -> HashMap<Url, f64> {
+    let min = scores.values().cloned().fold(f64::INFINITY, f64::min);
+    let max = scores.values().cloned().fold(f64::NEG_INFINITY, f64::max);
+    scores
+        .into_iter()
+        .map(|(url, score)| {
+            if (max - min).abs() < f64::EPSILON {
+                (url, 1.0)
+            } else {
+                (url, 0.1 + 0.9 * (score - min) / (max - min))
+            }
+        })
+        .collect()
+}
+
+/// Cuts the URL down to its origin and the first `depth` path segments.
+fn collapse_label(url: &Url, depth: Option<usize>) -> String {
+    let depth = match depth {
+        Some(depth) => depth,
+        None => {
+            return String::from(url.as_str());
+        }
+    };
+    let mut label = url.origin().ascii_serialization();
+    let segments: Vec<&str> = match url.path_segments() {
+        Some(segments) => segments.filter(|s| !s.is_empty()).collect(),
+        None => Vec::new(),
+    };
+    if segments.len() <= depth {
+        label.push_str(url.path());
+    } else {
+        label.push('/');
+        for segment in segments.iter().take(depth) {
+            label.push_str(segment);
+            label.push('/');
+        }
+    }
+    label
+}
+
+impl LinkGraph {
+    fn collapse(&self, depth: Option<usize>) -> Collapsed {
+        let mut graph: Collapsed = BTreeMap::new();
+        for node in self.nodes.iter() {
+            graph.entry(collapse_label(node, depth)).or_default();
+        }
+        for (from, targets) in self.edges.iter() {
+            let from = collapse_label(from, depth);
+            for to in targets.iter() {
+                let to = collapse_label(to, depth);
+                if from == to {
+                    continue;
+                }
+                *graph.entry(from.clone()).or_default().entry(to).or_insert(0) += 1;
+            }
+        }
+        graph
+    }
+
+    pub fn write_dot<W: Write>(&self, out: &mut W, depth: Option<usize>) -> io::Result<()> {
+        let graph = self.collapse(depth);
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        writeln!(out, "digraph sitemap {{")?;
+        for node in graph.keys() {
+            writeln!(out, "  {};", quote(node))?;
+        }
+        for (from, targets) in graph.iter() {
+            for (to, weight) in targets.iter() {
+                if depth.is_some() {
+                    writeln!(out, "  {} -> {} [weight={}, label=\"{}\"];", quote(from), quote(to), weight, weight)?;
+                } else {
+                    writeln!(out, "  {} -> {};", quote(from), quote(to))?;
+                }
+            }
+        }
+        writeln!(out, "}}")
+    }
+
+    pub fn write_graphml<W: Write>(&self, out: &mut W, depth: Option<usize>) -> io::Result<()> {
+        let graph = self.collapse(depth);
+        let mut writer = EmitterConfig::new().perform_indent(true).create_writer(out);
+        let to_io = |e: xml::writer::Error| io::Error::other(e.to_string());
+        writer
+            .write(XmlEvent::start_element("graphml").default_ns("http://graphml.graphdrawing.org/xmlns"))
+            .map_err(to_io)?;
+        writer
+            .write(
+                XmlEvent::start_element("key")
+                    .attr("id", "weight")
+                    .attr("for", "edge")
+                    .attr("attr.name", "weight")
+                    .attr("attr.type", "int"),
+            )
+            .map_err(to_io)?;
+        writer.write(XmlEvent::end_element()).map_err(to_io)?;
+        writer
+            .write(XmlEvent::start_element("graph").attr("id", "sitemap").attr("edgedefault", "directed"))
+            .map_err(to_io)?;
+        for node in graph.keys() {
+            writer.write(XmlEvent::start_element("node").attr("id", node)).map_err(to_io)?;
+            writer.write(XmlEvent::end_element()).map_err(to_io)?;
+        }
+        for (from, targets) in graph.iter() {
+            for (to, weight) in targets.iter() {
+                writer
+                    .write(XmlEvent::start_element("edge").attr("source", from).attr("target", to))
+                    .map_err(to_io)?;
+                writer
+                    .write(XmlEvent::start_element("data").attr("key", "weight"))
+                    .map_err(to_io)?;
+                writer.write(XmlEvent::characters(&weight.to_string())).map_err(to_io)?;
+                writer.write(XmlEvent::end_element()).map_err(to_io)?;
+                writer.write(XmlEvent::end_element()).map_err(to_io)?;
+            }
+        }
+        writer.write(XmlEvent::end_element()).map_err(to_io)?;
+        writer.write(XmlEvent::end_element()).map_err(to_io)?;
+        Ok(())
+    }
+
+    /// Adjacency list: every node maps to the nodes it links to, with weights when collapsed.
+    pub fn write_json<W: Write>(&self, out: &mut W, depth: Option<usize>) -> io::Result<()> {
+        let graph = self.collapse(depth);
+        let mut adjacency = Map::new();
+        for (from, targets) in graph.into_iter() {
+            let targets: Value = if depth.is_some() {
+                Value::Object(targets.into_iter().map(|(to, weight)| (to, json!(weight))).collect())
+            } else {
+                Value::Array(targets.into_keys().map(Value::String).collect())
+            };
+            adjacency.insert(from, targets);
+        }
+        serde_json::to_writer_pretty(&mut *out, &Value::Object(adjacency))?;
+        writeln!(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(path: &str) -> Url {
+        Url::parse("https://foo.bar/").unwrap().join(path).unwrap()
+    }
+
+    /// `/` links to `/a` and `/b`, both link back, `/a` also links to `/b`.
+    fn graph() -> LinkGraph {
+        let mut graph = LinkGraph::new();
+        graph.add_edge(&url("/"), &url("/a"));
+        graph.add_edge(&url("/"), &url("/b"));
+        graph.add_edge(&url("/a"), &url("/"));
+        graph.add_edge(&url("/a"), &url("/b"));
+        graph.add_edge(&url("/b"), &url("/"));
+        graph.add_edge(&url("/b"), &url("/b"));
+        graph
+    }
+
+    #[test]
+    fn inlinks_within_the_nodes() {
+        let graph = graph();
+        let (root, a, b) = (url("/"), url("/a"), url("/b"));
+        let counts = graph.inlink_counts(&vec![&root, &a, &b].into_iter().collect());
+        assert_eq!((counts[&root], counts[&a], counts[&b]), (2, 1, 2));
+        let counts = graph.inlink_counts(&vec![&root, &b].into_iter().collect());
+        assert_eq!((counts[&root], counts[&b]), (1, 1));
+        assert!(!counts.contains_key(&a));
+    }
+
+    #[test]
+    fn page_rank_sums_to_one() {
+        let graph = graph();
+        let (root, a, b) = (url("/"), url("/a"), url("/b"));
+        let rank = graph.page_rank(&vec![&root, &a, &b].into_iter().collect());
+        assert!((rank.values().sum::<f64>() - 1.0).abs() < 1e-6);
+        assert!(rank[&root] > rank[&b] && rank[&b] > rank[&a]);
+        assert!(graph.page_rank(&HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn scaling() {
+        let scores = vec![(url("/"), 4.0), (url("/a"), 1.0), (url("/b"), 2.0)].into_iter().collect();
+        let scaled = scale_to_priority(scores);
+        assert!((scaled[&url("/")] - 1.0).abs() < 1e-9);
+        assert!((scaled[&url("/a")] - 0.1).abs() < 1e-9);
+        assert!((scaled[&url("/b")] - 0.4).abs() < 1e-9);
+        let equal = scale_to_priority(vec![(url("/"), 3.0), (url("/a"), 3.0)].into_iter().collect());
+        assert!(equal.values().all(|p| (p - 1.0).abs() < 1e-9));
+    }
+
+    fn export(write: fn(&LinkGraph, &mut Vec<u8>, Option<usize>) -> io::Result<()>, depth: Option<usize>) -> String {
+        let mut graph = LinkGraph::new();
+        graph.add_edge(&url("/docs/a"), &url("/blog/x"));
+        graph.add_edge(&url("/docs/b"), &url("/blog/y"));
+        graph.add_edge(&url("/docs/a"), &url("/docs/b"));
+        let mut out = Vec::new();
+        write(&graph, &mut out, depth).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn collapse_labels() {
+        assert_eq!(collapse_label(&url("/a/b/c"), None), "https://foo.bar/a/b/c");
+        assert_eq!(collapse_label(&url("/a/b/c"), Some(1)), "https://foo.bar/a/");
+        assert_eq!(collapse_label(&url("/a/b"), Some(2)), "https://foo.bar/a/b");
+        assert_eq!(collapse_label(&url("/a/b/c?x=1"), Some(0)), "https://foo.bar/");
+    }
+
+    #[test]
+    fn dot_export() {
+        let dot = export(|g, out, depth| g.write_dot(out, depth), Some(1));
+        assert_eq!(
+            dot,
+            "digraph sitemap {\n  \"https://foo.bar/blog/\";\n  \"https://foo.bar/docs/\";\n  \"https://foo.bar/docs/\" -> \"https://foo.bar/blog/\" [weight=2, label=\"2\"];\n}\n"
+        );
+        let dot = export(|g, out, depth| g.write_dot(out, depth), None);
+        assert!(dot.contains("  \"https://foo.bar/docs/a\" -> \"https://foo.bar/docs/b\";\n"));
+    }
+
+    #[test]
+    fn json_and_graphml_export() {
+        let json: Value = serde_json::from_str(&export(|g, out, depth| g.write_json(out, depth), Some(1))).unwrap();
+        assert_eq!(json, json!({ "https://foo.bar/blog/": {}, "https://foo.bar/docs/": { "https://foo.bar/blog/": 2 } }));
+        let json: Value = serde_json::from_str(&export(|g, out, depth| g.write_json(out, depth), None)).unwrap();
+        assert_eq!(json["https://foo.bar/docs/a"], json!(["https://foo.bar/blog/x", "https://foo.bar/docs/b"]));
+        let graphml = export(|g, out, depth| g.write_graphml(out, depth), Some(1));
+        assert!(graphml.contains("<node id=\"https://foo.bar/docs/\" />"));
+        assert!(graphml.contains("<edge source=\"https://foo.bar/docs/\" target=\"https://foo.bar/blog/\">"));
+        assert!(graphml.contains("<data key=\"weight\">2</data>"));
+    }
+}
@@ -0,0 +1,242 @@
+use crate::options::{LogFormat, LogLevel, LogMode, Options};
+use chrono::{SecondsFormat, Utc};
+use reqwest::Url;
+use serde_json::{json, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// One requested URL, written as a single line of the log.
+pub struct FetchEvent<'a> {
+    pub url: &'a Url,
+    pub status: Option<u16>,
+    pub content_type: Option<&'a str>,
+    pub bytes: Option<u64>,
+    pub duration: Duration,
+    pub depth: usize,
+    pub referrer: Option<&'a Url>,
+    /// How the referrer linked to the url (`<a href>`, `<iframe src>`, `pdf`, ...), `seed` for seeds.
+    pub source: Option<&'a str>,
+    pub decision: &'static str,
+}
+
+pub struct Logger {
+    out: BufWriter<File>,
+    level: LogLevel,
+    format: LogFormat,
+    error: Option<io::Error>,
+}
+
+/// Moves `log` to `log.1`, `log.1` to `log.2` and so on, dropping the oldest one.
+fn rotate(path: &str, keep: usize) -> io::Result<()> {
+    if keep == 0 || !Path::new(path).exists() {
+        return Ok(());
+    }
+    for i in (1..keep).rev() {
+        let from = format!("{}.{}", path, i);
+        if Path::new(&from).exists() {
+            fs::rename(&from, format!("{}.{}", path, i + 1))?;
+        }
+    }
+    fs::rename(path, format!("{}.1", path))
+}
+
+impl Logger {
+    pub fn open(options: &Options) -> io::Result<Logger> {
+        let path = options.log_file.as_str();
+        let file = match options.log_mode {
+            LogMode::Truncate => File::create(path)?,
+            LogMode::Append => OpenOptions::new().create(true).append(true).open(path)?,
+            LogMode::Rotate => {
+                rotate(path, options.log_keep)?;
+                File::create(path)?
+            }
+        };
+        Ok(Logger {
+            out: BufWriter::new(file),
+            level: options.log_level,
+            format: options.log_format,
+            error: None,
+        })
+    }
+
+    pub fn enabled(&self, level: LogLevel) -> bool {
+        level <= self.level
+    }
+
+    fn write_line(&mut self, line: String) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = writeln!(self.out, "{}", line) {
+            self.error = Some(e);
+        }
+    }
+
+    fn write(&mut self, level: LogLevel, message: &str, fields: Option<Value>) {
+        if !self.enabled(level) {
+            return;
+        }
+        let ts = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        let line = match self.format {
+            LogFormat::Text => format!("{} {:<5} {}", ts, level.name().to_uppercase(), message),
+            LogFormat::Json => {
+                let mut event = json!({ "ts": ts, "level": level.name(), "msg": message });
+                if let (Some(Value::Object(fields)), Value::Object(event)) = (fields, &mut event) {
+                    event.extend(fields);
+                }
+                event.to_string()
+            }
+        };
+        self.write_line(line);
+    }
+
+    pub fn error(&mut self, message: &str) {
+        self.write(LogLevel::Error, message, None);
+    }
+
+    pub fn warn(&mut self, message: &str) {
+        self.write(LogLevel::Warn, message, None);
+    }
+
+    pub fn info(&mut self, message: &str) {
+        self.write(LogLevel::Info, message, None);
+    }
+
+    pub fn debug(&mut self, message: &str) {
+        self.write(LogLevel::Debug, message, None);
+    }
+
+    pub fn fetch(&mut self, event: &FetchEvent) {
+        if self.format == LogFormat::Text {
+            let mut message = format!("Fetched '{}' decision={} depth={}", event.url, event.decision, event.depth);
+            if let Some(status) = event.status {
+                message += &format!(" status={}", status);
+            }
+            if let Some(content_type) = event.content_type {
+                message += &format!(" type={}", content_type);
+            }
+            if let Some(bytes) = event.bytes {
+                message += &format!(" bytes={}", bytes);
+            }
+            message += &format!(" duration={}ms", event.duration.as_millis());
+            if let Some(referrer) = event.referrer {
+                message += &format!(" referrer='{}'", referrer);
+            }
+            if let Some(source) = event.source {
+                message += &format!(" source='{}'", source);
+            }
+            self.write(LogLevel::Info, &message, None);
+            return;
+        }
+        let fields = json!({
+            "event": "fetch",
+            "url": event.url.as_str(),
+            "status": event.status,
+            "content_type": event.content_type,
+            "bytes": event.bytes,
+            "duration_ms": event.duration.as_millis() as u64,
+            "depth": event.depth,
+            "referrer": event.referrer.map(|r| r.as_str()),
+            "source": event.source,
+            "decision": event.decision,
+        });
+        self.write(LogLevel::Info, "fetch", Some(fields));
+    }
+
+    /// Flushes the log and reports the first write error, if there was one.
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn log_options(name: &str, settings: &[(&str, &str)]) -> Options {
+        let path = env::temp_dir().join(format!("xml_sitemapper_{}_{}.log", name, std::process::id()));
+        let mut options = Options::default();
+        options.set("log_file", path.to_str().unwrap()).unwrap();
+        for (key, value) in settings.iter() {
+            options.set(key, value).unwrap();
+        }
+        options
+    }
+
+    fn event<'a>(url: &'a Url, referrer: &'a Url) -> FetchEvent<'a> {
+        FetchEvent {
+            url,
+            status: Some(200),
+            content_type: Some("text/html"),
+            bytes: Some(42),
+            duration: Duration::from_millis(7),
+            depth: 1,
+            referrer: Some(referrer),
+            source: Some("<a href>"),
+            decision: "added",
+        }
+    }
+
+    #[test]
+    fn text_lines_below_the_level() {
+        let options = log_options("text", &[("log_level", "info")]);
+        let mut log = Logger::open(&options).unwrap();
+        log.debug("hidden");
+        log.warn("careful");
+        let (url, referrer) = (Url::parse("https://foo.bar/a").unwrap(), Url::parse("https://foo.bar/").unwrap());
+        log.fetch(&event(&url, &referrer));
+        log.finish().unwrap();
+        let content = fs::read_to_string(&options.log_file).unwrap();
+        fs::remove_file(&options.log_file).unwrap();
+        let lines: Vec<&str> = content.lines().map(|l| l.split_once(' ').unwrap().1).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "WARN  careful",
+                "INFO  Fetched 'https://foo.bar/a' decision=added depth=1 status=200 type=text/html bytes=42 duration=7ms referrer='https://foo.bar/' source='<a href>'",
+            ]
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        let options = log_options("json", &[("log_format", "json")]);
+        let mut log = Logger::open(&options).unwrap();
+        let (url, referrer) = (Url::parse("https://foo.bar/a").unwrap(), Url::parse("https://foo.bar/").unwrap());
+        log.fetch(&event(&url, &referrer));
+        log.finish().unwrap();
+        let content = fs::read_to_string(&options.log_file).unwrap();
+        fs::remove_file(&options.log_file).unwrap();
+        let line: Value = serde_json::from_str(content.trim()).unwrap();
+        assert_eq!(line["level"], "info");
+        assert_eq!(line["event"], "fetch");
+        assert_eq!(line["url"], "https://foo.bar/a");
+        assert_eq!(line["duration_ms"], 7);
+        assert_eq!(line["source"], "<a href>");
+    }
+
+    #[test]
+    fn rotation_keeps_the_newest_logs() {
+        let options = log_options("rotate", &[("log_mode", "rotate"), ("log_keep", "2")]);
+        let path = options.log_file.clone();
+        for run in 1..=4 {
+            let mut log = Logger::open(&options).unwrap();
+            log.error(&format!("run {}", run));
+            log.finish().unwrap();
+        }
+        let last_line = |path: &str| String::from(fs::read_to_string(path).unwrap().trim_end().rsplit(' ').next().unwrap());
+        assert_eq!(last_line(&path), "4");
+        assert_eq!(last_line(&format!("{}.1", path)), "3");
+        assert_eq!(last_line(&format!("{}.2", path)), "2");
+        assert!(!Path::new(&format!("{}.3", path)).exists());
+        for file in [path.clone(), format!("{}.1", path), format!("{}.2", path)].iter() {
+            fs::remove_file(file).unwrap();
+        }
+    }
+}
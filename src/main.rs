@@ -1,291 +1,19 @@
-use chrono::{SecondsFormat, Utc};
-use regex::Regex;
-use reqwest::{StatusCode, Url};
-use select::document::Document;
-use select::predicate::Name;
+use chrono::{DateTime, SecondsFormat};
+use reqwest::Url;
 use std::env;
 use std::fs::File;
-use std::io::{prelude::*, BufReader, BufWriter, Write};
-use std::{collections::HashMap, collections::HashSet, collections::VecDeque, thread};
+use std::io::{prelude::*, BufReader};
+use std::path::Path;
+use std::{collections::BTreeMap, collections::HashMap, collections::HashSet, process};
 
-mod terminal_writer;
-use terminal_writer::TermWriter;
-
-mod xml_file_writer;
-use xml_file_writer::XmlWriter;
-
-pub struct Mapper {
-    main_url: Url,
-    disallowed_extensions: HashSet<String>,
-    change_prio: HashMap<String, f64>,
-    delay: u64,
-    term: TermWriter,
-}
-
-impl Mapper {
-    pub fn new(main_url: Url, disallowed_extensions: HashSet<String>, change_prio: HashMap<String, f64>, delay: u64, terminal: TermWriter) -> Mapper {
-        Mapper{main_url, disallowed_extensions, change_prio, delay, term: terminal}
-    }
-
-    fn start_logging(&mut self, links: i64, map_len: usize, file_writer: &mut BufWriter<&mut File>) {
-        self.term.start_progress(links, map_len);
-        match writeln!(
-            file_writer,
-            "Crawling start: [{}, {}]",
-            Utc::now().date(),
-            Utc::now().time().format("%H:%M:%S")
-        ) {
-            Ok(_) => {
-                // OK
-            },
-            Err(_) => {
-                // UNABLE TO WRITE LOG
-            },
-        }
-    }
-
-    fn log_progress(&mut self, links: i64, map_len: usize, file_writer: &mut BufWriter<&mut File>) {
-        match writeln!(
-            file_writer,
-            "\nSize of queue on this iteration: {}",
-            links
-        ) {
-            Ok(_) => {
-                // OK
-            },
-            Err(_) => {
-                // UNABLE TO WRITE LOG
-            }
-        }
-        self.term.print_progress(links, map_len);
-    }
-
-    fn normalize_url(&self, url: Url) -> Option<Url> {
-        let norm = url_normalizer::normalize(url);
-        match norm {
-            Ok(mut norm) => {
-                let u = norm.set_scheme(self.main_url.scheme());
-                match u {
-                    Ok(_) => {
-                        Some(norm)
-                    },
-                    Err(_) => {
-                        None
-                    }
-                }
-            }
-            Err(_) => {
-                None
-            }
-        }
-    }
-
-    fn priority_changes_segment_count(&self, mut priority: f64, url: &Url) -> f64 {
-        let seg = url.path_segments();
-        match seg {
-            Some(seg) => {
-                priority -= 0.1 * (seg.count() as f64 - 1.0 + url.query_pairs().count() as f64);
-            }
-            None => {
-                priority -= 0.1 * (url.query_pairs().count() as f64);
-            }
-        }
-        priority
-    }
-
-    fn update_map(&self, map: &mut HashMap<Url, f64>, url: &Url, priority: &mut f64, file_writer: &mut BufWriter<&mut File>) {
-        if !map.contains_key(&url) {
-            let url_str = url.as_str();
-            for i in self.change_prio.iter() {
-                let re = Regex::new(i.0);
-                match re {
-                    Ok(re) => {
-                        if re.is_match(url_str) {
-                            *priority += i.1;
-                        }
-                    }
-                    Err(_) => {
-                        writeln!(
-                            file_writer,
-                            "Error while parsing a regex from disallow.cfg: {}",
-                            i.0
-                        );
-                    }
-                }
-            }
-            if *priority < 0.1 {
-                *priority = 0.1;
-            }
-            map.insert(url.clone(), *priority);
-        }
-    }
-
-    fn get_body(&self, url: &Url, file_writer: &mut BufWriter<&mut File>) -> Option<reqwest::blocking::Response> {
-        let client = reqwest::blocking::Client::new();
-        let client = client.get(url.clone()).send();
-        let body: reqwest::blocking::Response;
-        match client {
-            Ok(res) => {
-                body = res;
-            }
-            Err(_) => {
-                return None;
-            }
-        }
-        match body.status() {
-            StatusCode::OK => {
-                writeln!(file_writer, "Successfully pinged '{}'.", url);
-                Some(body)
-            }
-            s => {
-                writeln!(file_writer, "Received {} status code, skipping...", s);
-                None
-            }
-        }
-    }
-
-    fn check_header(&self, body: &reqwest::blocking::Response) -> bool {
-        match body.headers().get("Content-Type") {
-            Some(url_check) => {
-                match url_check.to_str() {
-                    Ok(st) => {
-                        if String::from(st).starts_with("text/html") {
-                            return true;
-                        } else {
-                            return false;
-                        }
-                    }
-                    Err(_) => {
-                        return false;
-                    }
-                }
-            }
-            None => {
-                return false;
-            }
-        }
-    }
-
-    fn check_disallowed(&self, link: &str, file_writer: &mut BufWriter<&mut File>) -> bool {
-        let mut flag = false;
-        for i in self.disallowed_extensions.iter() {
-            let re = Regex::new(i);
-            match re {
-                Ok(re) => {
-                    if re.is_match(link) {
-                        flag = true;
-                    }
-                }
-                Err(_) => {
-                    writeln!(
-                        file_writer,
-                        "Error while parsing a regex from disallow.cfg: {}",
-                        i
-                    );
-                }
-            }
-        }
-        flag
-    }
-
-    fn scan_link(&mut self, map: &mut HashMap<Url, f64>, log: &mut File) {
-        let mut file_writer = BufWriter::new(log);
-        let mut queue: VecDeque<Url> = VecDeque::new();
-        let mut set: HashSet<Url> = HashSet::new();
-        let mut links: i64 = 1;
-        queue.push_front(self.main_url.clone());
-        set.insert(self.main_url.clone());
-        self.start_logging(links, map.len(), &mut file_writer);
-        while !queue.is_empty() {
-            self.log_progress(links, map.len(), &mut file_writer);
-            let ten_millis = std::time::Duration::from_millis(self.delay);
-            thread::sleep(ten_millis);
-            let queue_pop = queue.pop_back();
-            links -= 1;
-            let mut url: Url;
-            match queue_pop {
-                Some(queue_pop) => {
-                    url = queue_pop;
-                }
-                None => {
-                    continue;
-                }
-            }
-            match self.normalize_url(url) {
-                Some(normalized) => {
-                    url = normalized;
-                },
-                None => {
-                    continue;
-                }
-            }
-            if url.domain() != self.main_url.domain() {
-                continue;
-            }
-            writeln!(&mut file_writer, "\nWorking with '{}' now", url.as_str());
-            let mut priority: f64 = 1.0;
-            priority = self.priority_changes_segment_count(priority, &url);
-            let mut body: reqwest::blocking::Response;
-            match self.get_body(&url, &mut file_writer) {
-                Some(result) => {
-                    body = result;
-                },
-                None => {
-                    continue;
-                }
-            }
-            self.update_map(map, &url, &mut priority, &mut file_writer);
-            match self.check_header(&body) {
-                false => {
-                    continue;
-                },
-                true => {},
-            }
-            let body = body.text();
-            match body {
-                Ok(body) => {
-                    let html = body;
-                    let html = Document::from(html.as_str());
-                    html.find(Name("a"))
-                        .filter_map(|h| h.attr("href"))
-                        .for_each(|link| {
-                            if ((link.starts_with('/')) && (link != "/"))
-                                || (link.starts_with(self.main_url.as_str()))
-                            {
-                                let flag = self.check_disallowed(link, &mut file_writer);
-                                let link = self.main_url.join(link).unwrap();
-                                if !flag {
-                                    let link = url_normalizer::normalize(link);
-                                    match link {
-                                        Ok(link) => {
-                                            if !set.contains(&link) {
-                                                links += 1;
-                                                set.insert(link.clone());
-                                                queue.push_front(link);
-                                            }
-                                        }
-                                        Err(_) => {}
-                                    }
-                                }
-                            }
-                        });
-                }
-                Err(_) => {}
-            }
-        }
-        writeln!(
-            &mut file_writer,
-            "Crawling end: [{}]\nBuilding file sitemap.xml.",
-            Utc::now().time().format("%H:%M:%S")
-        );
-    }
-
-    pub fn generate_sitemap(&mut self, mut log: &mut File) -> HashMap<Url, f64> {
-        let mut result_map = HashMap::<Url, f64>::new();
-        self.scan_link(&mut result_map, &mut log);
-        result_map
-    }
-}
+use xml_sitemapper::logger::Logger;
+use xml_sitemapper::metrics::SharedMetrics;
+use xml_sitemapper::options::{DedupeMode, GraphFormat, LastmodMode, Options, OutputFormat, SitemapOrder, OPTIONS_TEMPLATE};
+use xml_sitemapper::output::{self, OutputEntry};
+use xml_sitemapper::link_graph::LinkGraph;
+use xml_sitemapper::rewrite::Rewriter;
+use xml_sitemapper::terminal_writer::TermWriter;
+use xml_sitemapper::{duplicates, http_client, sitemap_diff, url_list, validate, Mapper};
 
 fn read_disallowed_exts(term: &TermWriter, exts: &mut HashSet<String>) {
     let disallowed = File::open("disallow.cfg");
@@ -307,26 +35,22 @@ fn read_disallowed_exts(term: &TermWriter, exts: &mut HashSet<String>) {
             exts.insert(String::from(".*/.*print=Y"));
         }
         Err(_) => {
-            term.print_to_term(format!(
-                "File disallow.cfg was not found, creating one instead."
-            ));
+            term.print_to_term(String::from("File disallow.cfg was not found, creating one instead."));
             let chk = File::create("disallow.cfg");
             match chk {
                 Ok(_) => {
-                    term.print_to_term(format!("==="));
-                    term.print_to_term(format!("Created file disallow.cfg."));
-                    term.print_to_term(format!("This file is used to contain all url parts which should be excluded from sitemap."));
-                    term.print_to_term(format!("For example, if .*.pdf is on the list then link 'https://foo.com/bar.pdf' won't be included in the sitemap."));
-                    term.print_to_term(format!(
-                        "You can also write comments in site.cfg starting the lines with #.",
-                    ));
+                    term.print_to_term(String::from("==="));
+                    term.print_to_term(String::from("Created file disallow.cfg."));
+                    term.print_to_term(String::from("This file is used to contain all url parts which should be excluded from sitemap."));
+                    term.print_to_term(String::from("For example, if .*.pdf is on the list then link 'https://foo.com/bar.pdf' won't be included in the sitemap."));
+                    term.print_to_term(String::from("You can also write comments in site.cfg starting the lines with #."));
                     let data = "# For example, you can exclude all URLs leading to .png images by writing a regex with it.
 # To do this, you should write one line per each file type (note, that mapper is case sensetive, so .png and .PNG are different file types for it)\n# The result should look like next line without '# ':
 # .*.png\n# This will make all URLs like https://foo.bar/cool_image.png excluded from sitemap.xml.";
                     std::fs::write("disallow.cfg", data).expect("Unable to write file");
                 }
                 Err(_) => {
-                    term.print_to_term(format!("Unable to create file disallow.cfg."));
+                    term.print_to_term(String::from("Unable to create file disallow.cfg."));
                 }
             }
         }
@@ -345,11 +69,8 @@ fn read_priority_changes(term: &TermWriter, chng: &mut HashMap<String, f64>) {
                         if !line.starts_with("#") && prev == "#" {
                             prev = line;
                         } else if !line.starts_with("#") && prev != "#" {
-                            match line.parse::<f64>() {
-                                Ok(flt) => {
-                                    chng.insert(prev.clone(), flt);
-                                }
-                                Err(_) => {}
+                            if let Ok(flt) = line.parse::<f64>() {
+                                chng.insert(prev.clone(), flt);
                             }
                             prev = String::from("#");
                         }
@@ -361,29 +82,23 @@ fn read_priority_changes(term: &TermWriter, chng: &mut HashMap<String, f64>) {
             }
         }
         Err(_) => {
-            term.print_to_term(format!(
-                "File change_prio.cfg not found, creating one instead."
-            ));
+            term.print_to_term(String::from("File change_prio.cfg not found, creating one instead."));
             let chk = File::create("change_prio.cfg");
             match chk {
                 Ok(_) => {
-                    term.print_to_term(format!("==="));
-                    term.print_to_term(format!("Created file change_prio.cfg."));
-                    term.print_to_term(format!("This file is used to show which url containing listed lines should be lowered or increased in priority."));
-                    term.print_to_term(format!("For each one the first line should contain the part of url written with regex and the second one should contain the number"));
-                    term.print_to_term(format!(
-                        "For example 0.5 for raise priority for 0.5 or -0.3 to lower it by 0.3.",
-                    ));
-                    term.print_to_term(format!(
-                        "You can also write comments in site.cfg starting the lines with #.",
-                    ));
+                    term.print_to_term(String::from("==="));
+                    term.print_to_term(String::from("Created file change_prio.cfg."));
+                    term.print_to_term(String::from("This file is used to show which url containing listed lines should be lowered or increased in priority."));
+                    term.print_to_term(String::from("For each one the first line should contain the part of url written with regex and the second one should contain the number"));
+                    term.print_to_term(String::from("For example 0.5 for raise priority for 0.5 or -0.3 to lower it by 0.3."));
+                    term.print_to_term(String::from("You can also write comments in site.cfg starting the lines with #."));
                     let data = "# For example, you can lower priority for all URLs with PAGEN_1 contained in query.
 # To do this, you should write two lines, containing 1) regex containing PAGEN_1 2) priority change\n# The result should look like next two comment lines without '# ':
 # .*PAGEN_1.*\n# -0.2\n# This will lower priority for all pages like https://foo.bar/?PAGEN_1=50 by 0.2.";
                     std::fs::write("change_prio.cfg", data).expect("Unable to write file");
                 }
                 Err(_) => {
-                    term.print_to_term(format!("Unable to create file change_prio.cfg."));
+                    term.print_to_term(String::from("Unable to create file change_prio.cfg."));
                 }
             }
         }
@@ -417,10 +132,8 @@ fn read_site(term: &TermWriter, url: &mut String, delay: &mut u64) {
                     }
                     Err(_) => {
                         if flag {
-                            term.print_to_term(format!(
-                                "It seems, that sitemapper can't detect an URL in site.cfg.",
-                            ));
-                            term.print_to_term(format!("Please check if it is correct."));
+                            term.print_to_term(String::from("It seems, that sitemapper can't detect an URL in site.cfg."));
+                            term.print_to_term(String::from("Please check if it is correct."));
                         }
                         continue;
                     }
@@ -428,42 +141,331 @@ fn read_site(term: &TermWriter, url: &mut String, delay: &mut u64) {
             }
         }
         Err(_) => {
-            term.print_to_term(format!("File site.cfg not found, creating one instead."));
+            term.print_to_term(String::from("File site.cfg not found, creating one instead."));
             let chk = File::create("site.cfg");
             match chk {
                 Ok(_) => {
-                    term.print_to_term(format!("==="));
-                    term.print_to_term(format!("Created file site.cfg."));
-                    term.print_to_term(format!(
-                        "Now you can use site.cfg to store URL that is going to be mapped.",
-                    ));
-                    term.print_to_term(format!("You can specify the delay (in ms) between url requests in the next line. The default is 25 ms."));
-                    term.print_to_term(format!(
-                        "You can also write comments in site.cfg starting the lines with #.",
-                    ));
-                    term.print_to_term(format!("\nFill in this file and launch the mapper again."));
+                    term.print_to_term(String::from("==="));
+                    term.print_to_term(String::from("Created file site.cfg."));
+                    term.print_to_term(String::from("Now you can use site.cfg to store URL that is going to be mapped."));
+                    term.print_to_term(String::from("You can specify the delay (in ms) between url requests in the next line. The default is 25 ms."));
+                    term.print_to_term(String::from("You can also write comments in site.cfg starting the lines with #."));
+                    term.print_to_term(String::from("\nFill in this file and launch the mapper again."));
                     let data = "# Write your site root URL in the next line. Please, include protocol in the URL (http or https).\n
 # Write the delay in milliseconds between URL requests in the next line (this one is optional, but may be useful if your site blocks bots by number of requests per time fragment):\n";
                     std::fs::write("site.cfg", data).expect("Unable to write file");
-                    return;
                 }
                 Err(_) => {
-                    term.print_to_term(format!("Unable to create file site.cfg."));
+                    term.print_to_term(String::from("Unable to create file site.cfg."));
                 }
             }
         }
     }
 }
 
+fn read_options(term: &TermWriter, opts: &mut Options) {
+    let options = File::open("options.cfg");
+    match options {
+        Ok(options) => {
+            let options = BufReader::new(options);
+            for line in options.lines() {
+                match line {
+                    Ok(line) => {
+                        let line = line.trim();
+                        if line.is_empty() || line.starts_with('#') {
+                            continue;
+                        }
+                        match line.split_once('=') {
+                            Some((key, value)) => {
+                                if let Err(e) = opts.set(key.trim(), value.trim()) {
+                                    term.print_to_term(e);
+                                }
+                            }
+                            None => {
+                                term.print_to_term(format!("Line '{}' in options.cfg is not a 'key = value' pair, skipping.", line));
+                            }
+                        }
+                    }
+                    Err(_) => {
+                        continue;
+                    }
+                }
+            }
+        }
+        Err(_) => {
+            term.print_to_term(String::from("File options.cfg not found, creating one instead."));
+            match std::fs::write("options.cfg", OPTIONS_TEMPLATE) {
+                Ok(_) => {
+                    term.print_to_term(String::from("==="));
+                    term.print_to_term(String::from("Created file options.cfg."));
+                    term.print_to_term(String::from("This file contains optional settings written as 'key = value', all of them have defaults."));
+                    term.print_to_term(String::from("You can also write comments in options.cfg starting the lines with #."));
+                }
+                Err(_) => {
+                    term.print_to_term(String::from("Unable to create file options.cfg."));
+                }
+            }
+        }
+    }
+}
+
+/// Splits the map into the sitemap files to write, either one combined sitemap.xml
+/// or one sitemap_<host>[_<port>].xml per host.
+fn split_sitemaps(map: HashMap<Url, f64>, per_host: bool) -> Vec<(String, HashMap<Url, f64>)> {
+    if !per_host {
+        return vec![(String::from("sitemap.xml"), map)];
+    }
+    let mut hosts: HashMap<String, HashMap<Url, f64>> = HashMap::new();
+    for (url, priority) in map {
+        let mut host = String::from(url.host_str().unwrap_or("unknown"));
+        if let Some(port) = url.port() {
+            host = format!("{}_{}", host, port);
+        }
+        let host: String = host
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        hosts.entry(host).or_default().insert(url, priority);
+    }
+    hosts
+        .into_iter()
+        .map(|(host, entries)| (format!("sitemap_{}.xml", host), entries))
+        .collect()
+}
+
+/// Order and lastmod values of the written entries, so that the same crawl always gives the same file.
+struct SitemapLayout {
+    order: SitemapOrder,
+    lastmod: LastmodMode,
+    discovered: HashMap<Url, usize>,
+    last_modified: HashMap<Url, String>,
+    statuses: HashMap<Url, u16>,
+    crawl_time: String,
+}
+
+impl SitemapLayout {
+    fn new(opts: &Options, mapper: &Mapper, rewriter: &Rewriter, crawl_time: String) -> SitemapLayout {
+        let rewrite = |url: &Url| if rewriter.is_empty() { url.clone() } else { rewriter.rewrite(url) };
+        let mut discovered = HashMap::new();
+        for (url, index) in mapper.discovered().iter() {
+            // Rewriting can merge urls; keep the earliest position.
+            let position = discovered.entry(rewrite(url)).or_insert(*index);
+            *position = (*position).min(*index);
+        }
+        // Merged urls get the latest Last-Modified and the status of the first discovered one.
+        let mut last_modified: HashMap<Url, String> = HashMap::new();
+        for (url, modified) in mapper.last_modified().iter() {
+            let latest = last_modified.entry(rewrite(url)).or_insert_with(|| modified.clone());
+            if DateTime::parse_from_rfc3339(modified).ok() > DateTime::parse_from_rfc3339(latest).ok() {
+                *latest = modified.clone();
+            }
+        }
+        let mut statuses: Vec<(&Url, &u16)> = mapper.statuses().iter().collect();
+        statuses.sort_by_key(|(url, _)| (mapper.discovered().get(*url).copied().unwrap_or(usize::MAX), url.as_str()));
+        let mut first_statuses = HashMap::new();
+        for (url, status) in statuses {
+            first_statuses.entry(rewrite(url)).or_insert(*status);
+        }
+        SitemapLayout {
+            order: opts.sitemap_order,
+            lastmod: opts.lastmod,
+            discovered,
+            last_modified,
+            statuses: first_statuses,
+            crawl_time,
+        }
+    }
+
+    fn entries(&self, map: HashMap<Url, f64>) -> Vec<OutputEntry> {
+        let mut entries: Vec<(Url, f64)> = map.into_iter().collect();
+        match self.order {
+            SitemapOrder::Url => entries.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str())),
+            SitemapOrder::Priority => entries.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.as_str().cmp(b.0.as_str()))),
+            SitemapOrder::Discovery => entries.sort_by_key(|(url, _)| (self.discovered.get(url).copied().unwrap_or(usize::MAX), String::from(url.as_str()))),
+        }
+        entries
+            .into_iter()
+            .map(|(url, priority)| OutputEntry {
+                lastmod: self.lastmod(&url),
+                modified: self.last_modified.get(&url).cloned(),
+                status: self.statuses.get(&url).copied(),
+                url,
+                priority,
+            })
+            .collect()
+    }
+
+    fn lastmod(&self, url: &Url) -> Option<String> {
+        match self.lastmod {
+            LastmodMode::Crawl => Some(self.crawl_time.clone()),
+            LastmodMode::LastModified => self.last_modified.get(url).cloned(),
+            LastmodMode::None => None,
+        }
+    }
+}
+
+fn write_link_graph(term: &TermWriter, graph: &LinkGraph, opts: &Options, dir: &str) -> Result<(), String> {
+    for format in opts.graph_formats.iter() {
+        let file_name = String::from(dir) + format.file_name();
+        output::write_atomic(&file_name, |out| match format {
+            GraphFormat::Dot => graph.write_dot(out, opts.graph_collapse_depth),
+            GraphFormat::GraphMl => graph.write_graphml(out, opts.graph_collapse_depth),
+            GraphFormat::Json => graph.write_json(out, opts.graph_collapse_depth),
+        })
+        .map_err(|e| format!("Unable to write link graph to '{}': {}", file_name, e))?;
+        term.print_to_term(format!("Link graph written to '{}'.", file_name));
+    }
+    Ok(())
+}
+
+fn write_metrics(term: &TermWriter, metrics: &SharedMetrics, opts: &Options) {
+    if let Some(file) = &opts.metrics_file {
+        if let Err(e) = metrics.write_textfile(file) {
+            term.print_to_term(format!("Unable to write metrics to '{}': {}", file, e));
+        }
+    }
+}
+
+/// Reports a fatal error, writes the metrics of the failed run and exits with 1.
+fn fail(term: &TermWriter, metrics: &SharedMetrics, opts: &Options, mut log: Logger, message: &str) -> ! {
+    eprintln!("{}", message);
+    log.error(message);
+    metrics.update(|m| m.finish());
+    write_metrics(term, metrics, opts);
+    let _ = log.finish();
+    process::exit(1);
+}
+
+fn check_orphans(term: &TermWriter, mapper: &mut Mapper, known: &str, opts: &Options, map: &mut HashMap<Url, f64>, log: &mut Logger, dir: &str) -> Result<(), String> {
+    let known = match url_list::read_url_list(known) {
+        Ok(known) => known,
+        Err(e) => {
+            term.print_to_term(format!("Unable to read known urls from '{}': {}", known, e));
+            return Ok(());
+        }
+    };
+    let mut report = mapper.find_orphans(&known, map);
+    if opts.merge_orphans {
+        mapper.merge_orphans(&mut report, map, log);
+    }
+    term.print_to_term(format!(
+        "Orphans: {} ({} merged into sitemap), crawled urls missing from the known list: {}.",
+        report.orphans.len(),
+        report.merged.len(),
+        report.missing.len()
+    ));
+    let report_path = String::from(dir) + "orphans.txt";
+    report
+        .write(&report_path)
+        .map_err(|e| format!("Unable to write orphan report to '{}': {}", report_path, e))?;
+    term.print_to_term(format!("Orphan report written to '{}'.", report_path));
+    Ok(())
+}
+
+/// `diff <old> <new> [--local] [--json]`: prints the changes between two sitemaps or sitemap indexes.
+fn diff_command(args: &[String]) {
+    let files: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    if files.len() != 2 {
+        eprintln!("Usage: diff <old sitemap> <new sitemap> [--local] [--json]");
+        process::exit(2);
+    }
+    let (opts, client) = command_client();
+    let local = args.iter().any(|a| a == "--local");
+    let load = |file: &str| match sitemap_diff::load(file, &client, &opts, local) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    let diff = sitemap_diff::diff(&load(files[0]), &load(files[1]));
+    if args.iter().any(|a| a == "--json") {
+        println!("{:#}", diff.to_json());
+    } else {
+        for line in diff.lines() {
+            println!("{}", line);
+        }
+    }
+    if !diff.is_empty() {
+        process::exit(1);
+    }
+}
+
+/// Reads options.cfg, if there is one, without creating it and builds the client for
+/// the `diff` and `validate` commands. Messages go to stderr, stdout holds the report.
+fn command_client() -> (Options, reqwest::blocking::Client) {
+    let mut opts = Options::default();
+    if Path::new("options.cfg").exists() {
+        read_options(&TermWriter::stderr(true), &mut opts);
+    }
+    if opts.insecure {
+        eprintln!("WARNING: TLS certificate verification is disabled by 'insecure = true' in options.cfg.");
+    }
+    match http_client::build(&opts) {
+        Ok(client) => (opts, client),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    }
+}
+
+/// `validate <sitemap> [--fetch] [--local] [--json]`: checks a sitemap against the protocol and,
+/// with `--fetch`, every listed URL against the live site.
+fn validate_command(args: &[String]) {
+    let files: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    if files.len() != 1 {
+        eprintln!("Usage: validate <sitemap> [--fetch] [--local] [--json]");
+        process::exit(2);
+    }
+    let (opts, client) = command_client();
+    let mut report = validate::validate(files[0], &client, &opts, args.iter().any(|a| a == "--local"));
+    if args.iter().any(|a| a == "--fetch") {
+        let client = http_client::builder(&opts)
+            .and_then(|b| b.redirect(reqwest::redirect::Policy::none()).build().map_err(|e| e.to_string()));
+        match client {
+            Ok(client) => report.check_live(&client, &opts),
+            Err(e) => {
+                eprintln!("Unable to create the HTTP client: {}", e);
+                process::exit(2);
+            }
+        }
+    }
+    if args.iter().any(|a| a == "--json") {
+        println!("{:#}", report.to_json());
+    } else {
+        for line in report.lines() {
+            println!("{}", line);
+        }
+    }
+    if report.errors() > 0 {
+        process::exit(1);
+    }
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("diff") => {
+            diff_command(&args[2..]);
+            return;
+        }
+        Some("validate") => {
+            validate_command(&args[2..]);
+            return;
+        }
+        _ => {}
+    }
     let mut active_term = true;
     let mut path: Option<String> = None;
+    let mut known: Option<String> = None;
     for it in 0..args.len() {
         let arg = args[it].as_str();
         match arg {
             "--help" => {
-                println!("[-p <path>] [-s | --silent]");
+                println!("[-p <path>] [-s | --silent] [-k | --known <file with known urls>]");
+                println!("diff <old sitemap> <new sitemap> [--local] [--json]");
+                println!("validate <sitemap> [--fetch] [--local] [--json]");
                 return;
             }
             "--silent" => active_term = false,
@@ -473,27 +475,20 @@ fn main() {
                 None => {
                     path = None;
                     let term = TermWriter::new(true);
-                    term.print_to_term(format!("Found key -p which is not followed by a path, assuming path is executable's directory."));
+                    term.print_to_term(String::from("Found key -p which is not followed by a path, assuming path is executable's directory."));
+                }
+            },
+            "-k" | "--known" => match args.get(it + 1) {
+                Some(k) => known = Some(String::from(k)),
+                None => {
+                    let term = TermWriter::new(true);
+                    term.print_to_term(String::from("Found key -k which is not followed by a file, skipping orphan detection."));
                 }
             },
             _ => {}
         }
     }
     let term = TermWriter::new(active_term);
-    let fil: std::io::Result<File>;
-    let final_messg: String;
-    let logger = File::create("XmlSiteMapper-rs.log");
-    let file: File;
-    let mut log: File;
-    match logger {
-        Ok(logger) => {
-            log = logger;
-        }
-        Err(_) => {
-            term.print_to_term(format!("Cannot create file XmlSiteMapper-rs.log. Please check if file creation is allowed in the directory."));
-            return;
-        }
-    }
     let mut exts: HashSet<String> = HashSet::new();
     let mut chng: HashMap<String, f64> = HashMap::new();
 
@@ -504,75 +499,216 @@ fn main() {
     let mut url = String::new();
 
     read_site(&term, &mut url, &mut delay);
-    
+
+    let mut opts = Options::default();
+    read_options(&term, &mut opts);
+
+    let mut log = match Logger::open(&opts) {
+        Ok(log) => log,
+        Err(e) => {
+            eprintln!("Cannot create file {}: {}. Please check if file creation is allowed in the directory.", opts.log_file, e);
+            let metrics = SharedMetrics::new();
+            metrics.update(|m| m.finish());
+            write_metrics(&term, &metrics, &opts);
+            process::exit(1);
+        }
+    };
+
     let url = Url::parse(&url);
-    match url {
-        Ok(main_url) => {
-            term.print_to_term(format!(
-                "All necessary files checked, starting sitemap.xml generation."
-            ));
-            let mut mapper = Mapper::new(main_url, exts, chng, delay, term.clone());
-            let map = mapper.generate_sitemap(&mut log);
-            match path {
-                Some(path) => {
-                    final_messg = format!(
-                        "sitemap.xml generation is completed. You can find it here: '{}'.",
-                        String::from(&path) + "sitemap.xml"
-                    );
-                    fil = File::create(String::from(&path) + "sitemap.xml")
-                }
-                None => {
-                    final_messg = format!(
-                        "sitemap.xml generation is completed. You can find it in the same directory with the executable.",
-                    );
-                    fil = File::create("sitemap.xml");
+    if let Err(e) = &url {
+        fail(&term, &SharedMetrics::new(), &opts, log, &format!("Unable to read the site URL from site.cfg: {}.", e));
+    }
+    if let Ok(main_url) = url {
+        term.print_to_term(String::from("All necessary files checked, starting sitemap.xml generation."));
+        if opts.insecure {
+            term.print_to_term(String::from("WARNING: TLS certificate verification is disabled by 'insecure = true' in options.cfg."));
+            log.warn("TLS certificate verification is disabled.");
+        }
+        let client = match http_client::build(&opts) {
+            Ok(client) => client,
+            Err(e) => fail(&term, &SharedMetrics::new(), &opts, log, &e),
+        };
+        let site = main_url.clone();
+        let sitemap_client = client.clone();
+        let mut mapper = Mapper::new(main_url, exts, chng, delay, client, term.clone(), opts.clone());
+        if !mapper.root_in_scope() {
+            fail(&term, mapper.metrics(), &opts, log, &format!("The site URL '{}' in site.cfg lies outside the crawl scope, please check scope and scope_path_prefix in options.cfg.", site));
+        }
+        if let Some(addr) = &opts.metrics_listen {
+            match mapper.metrics().serve(addr) {
+                Ok(_) => log.info(&format!("Serving metrics on http://{}/metrics", addr)),
+                Err(e) => term.print_to_term(format!("Unable to serve metrics on '{}': {}", addr, e)),
+            }
+        }
+        let mut map = mapper.generate_sitemap(&mut log);
+        let dir = path.clone().unwrap_or_default();
+        if let Some(known) = &known {
+            if let Err(e) = check_orphans(&term, &mut mapper, known, &opts, &mut map, &mut log, &dir) {
+                fail(&term, mapper.metrics(), &opts, log, &e);
+            }
+        }
+        if opts.dedupe != DedupeMode::Off {
+            let report_path = String::from(&dir) + "duplicates.txt";
+            term.print_to_term(format!("Duplicate groups found: {}.", mapper.duplicates().len()));
+            if let Err(e) = duplicates::write_report(mapper.duplicates(), &report_path) {
+                fail(&term, mapper.metrics(), &opts, log, &format!("Unable to write duplicates report to '{}': {}", report_path, e));
+            }
+        }
+        let rewriter = Rewriter::new(&opts);
+        if !rewriter.is_empty() {
+            map = rewriter.rewrite_map(map);
+        }
+        let crawl_time = mapper.stats().started.to_rfc3339_opts(SecondsFormat::Secs, false);
+        let layout = SitemapLayout::new(&opts, &mapper, &rewriter, crawl_time);
+        term.print_to_term(format!("\n=====\nTotal urls added: {}\n=====", map.len()));
+        for line in mapper.stats().summary(map.len()) {
+            term.print_to_term(line);
+        }
+        let urls_added = map.len();
+        mapper.metrics().update(|m| {
+            m.sitemap_urls = urls_added;
+            m.finish();
+        });
+        if opts.stats_json {
+            let stats_path = String::from(&dir) + "stats.json";
+            if let Err(e) = mapper.stats().write_json(&stats_path, map.len()) {
+                fail(&term, mapper.metrics(), &opts, log, &format!("Unable to write crawl statistics to '{}': {}", stats_path, e));
+            }
+        }
+        let mut previous = BTreeMap::new();
+        let mut current = BTreeMap::new();
+        let diff_previous = opts.diff_previous && opts.output_formats.contains(&OutputFormat::Xml);
+        let formats: Vec<_> = opts.output_formats.iter().map(|f| output::format(*f, &opts, &site, mapper.stats().started)).collect();
+        for (sitemap_name, entries) in split_sitemaps(map, opts.sitemap_per_host) {
+            let sitemap_path = String::from(&dir) + &sitemap_name;
+            if diff_previous && Path::new(&sitemap_path).exists() {
+                match sitemap_diff::load(&sitemap_path, &sitemap_client, &opts, true) {
+                    Ok(old) => previous.extend(old),
+                    Err(e) => term.print_to_term(e),
                 }
             }
-            match fil {
-                Ok(fil) => {
-                    file = fil;
+            let entries = layout.entries(entries);
+            for format in formats.iter() {
+                let file_name = String::from(sitemap_name.trim_end_matches(".xml")) + format.extension();
+                let file_path = String::from(&dir) + &file_name;
+                if let Err(e) = format.write(&file_path, &entries) {
+                    fail(&term, mapper.metrics(), &opts, log, &format!("Unable to write {}: {}. Please check if file creation is allowed in the directory and there is enough free space.", file_path, e));
                 }
-                Err(_) => {
-                    term.print_to_term(format!("Cannot create file sitemap.xml. Please check if file creation is allowed in the directory."));
-                    return;
+                match &path {
+                    Some(path) => {
+                        term.print_to_term(format!(
+                            "{} generation is completed. You can find it here: '{}'.",
+                            file_name,
+                            String::from(path) + &file_name
+                        ));
+                    }
+                    None => {
+                        term.print_to_term(format!("{} generation is completed. You can find it in the same directory with the executable.", file_name));
+                    }
                 }
             }
-            term.print_to_term(format!("\n=====\nTotal urls added: {}\n=====", map.len()));
-            let mut writer = XmlWriter::new(file);
-            // TODO: Write a checker for xml writer Result<()>
-            writer.comment(String::from("=== Created with XmlSiteMapper-rs ==="));
-            writer.open_element_attr(
-                String::from("urlset"),
-                String::from("xmlns"),
-                String::from("http://www.sitemaps.org/schemas/sitemap/0.9"),
-            );
-            for (key, value) in map {
-                writer.open_element(String::from("url"));
-
-                writer.write_element(String::from("loc"), String::from(key.as_str()));
-
-                let now = Utc::now();
-
-                writer.write_element(
-                    String::from("lastmod"),
-                    String::from(now.to_rfc3339_opts(SecondsFormat::Secs, false)),
-                );
-
-                writer.write_element(String::from("priority"), format!("{0:.1}", value));
-
-                writer.close_element();
+            if diff_previous {
+                match sitemap_diff::load(&sitemap_path, &sitemap_client, &opts, true) {
+                    Ok(new) => current.extend(new),
+                    Err(e) => term.print_to_term(e),
+                }
             }
-            writer.close_element();
-            term.print_to_term(final_messg);
-            let mut file_writer = BufWriter::new(log);
-            writeln!(
-                &mut file_writer,
-                "Built file sitemap.xml: [{}]",
-                Utc::now().time().format("%H:%M:%S")
-            );
         }
-        Err(_) => {
-            return;
+        if diff_previous {
+            let diff = sitemap_diff::diff(&previous, &current);
+            term.print_to_term(diff.summary());
+            let diff_path = String::from(&dir) + "sitemap_diff.json";
+            if let Err(e) = diff.write_json(&diff_path) {
+                fail(&term, mapper.metrics(), &opts, log, &format!("Unable to write sitemap diff to '{}': {}", diff_path, e));
+            }
+        }
+        if let Err(e) = write_link_graph(&term, mapper.link_graph(), &opts, &dir) {
+            fail(&term, mapper.metrics(), &opts, log, &e);
+        }
+        log.info("Built file sitemap.xml.");
+        mapper.metrics().update(|m| m.success = true);
+        write_metrics(&term, mapper.metrics(), &opts);
+    }
+    if let Err(e) = log.finish() {
+        eprintln!("Unable to write the log to {}: {}", opts.log_file, e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn sitemap_per_host() {
+        let map: HashMap<Url, f64> = vec![
+            (url("https://foo.bar/"), 1.0),
+            (url("https://foo.bar/a"), 0.9),
+            (url("http://foo.bar:8080/"), 1.0),
+            (url("http://[::1]/"), 1.0),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(split_sitemaps(map.clone(), false), vec![(String::from("sitemap.xml"), map.clone())]);
+        let mut files: Vec<(String, usize)> = split_sitemaps(map, true).into_iter().map(|(name, urls)| (name, urls.len())).collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                (String::from("sitemap____1_.xml"), 1),
+                (String::from("sitemap_foo.bar.xml"), 2),
+                (String::from("sitemap_foo.bar_8080.xml"), 1),
+            ]
+        );
+    }
+
+    fn layout(order: SitemapOrder, lastmod: LastmodMode) -> SitemapLayout {
+        SitemapLayout {
+            order,
+            lastmod,
+            discovered: vec![(url("https://foo.bar/"), 0), (url("https://foo.bar/z"), 1), (url("https://foo.bar/b"), 2)].into_iter().collect(),
+            last_modified: vec![(url("https://foo.bar/z"), String::from("2024-01-02T00:00:00+00:00"))].into_iter().collect(),
+            statuses: vec![(url("https://foo.bar/z"), 200)].into_iter().collect(),
+            crawl_time: String::from("2024-03-01T00:00:00+00:00"),
         }
     }
+
+    fn map() -> HashMap<Url, f64> {
+        vec![
+            (url("https://foo.bar/b"), 0.9),
+            (url("https://foo.bar/"), 1.0),
+            (url("https://foo.bar/a"), 0.9),
+            (url("https://foo.bar/z"), 0.5),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn paths(entries: &[OutputEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.url.path()).collect()
+    }
+
+    #[test]
+    fn sitemap_order() {
+        assert_eq!(paths(&layout(SitemapOrder::Url, LastmodMode::None).entries(map())), vec!["/", "/a", "/b", "/z"]);
+        assert_eq!(paths(&layout(SitemapOrder::Priority, LastmodMode::None).entries(map())), vec!["/", "/a", "/b", "/z"]);
+        assert_eq!(paths(&layout(SitemapOrder::Discovery, LastmodMode::None).entries(map())), vec!["/", "/z", "/b", "/a"]);
+    }
+
+    #[test]
+    fn lastmod_modes() {
+        let entries = layout(SitemapOrder::Url, LastmodMode::LastModified).entries(map());
+        let lastmods: Vec<Option<&str>> = entries.iter().map(|e| e.lastmod.as_deref()).collect();
+        assert_eq!(lastmods, vec![None, None, None, Some("2024-01-02T00:00:00+00:00")]);
+        assert_eq!(entries[3].status, Some(200));
+        let entries = layout(SitemapOrder::Url, LastmodMode::Crawl).entries(map());
+        assert!(entries.iter().all(|e| e.lastmod.as_deref() == Some("2024-03-01T00:00:00+00:00")));
+        assert_eq!(entries[3].modified.as_deref(), Some("2024-01-02T00:00:00+00:00"));
+        let entries = layout(SitemapOrder::Url, LastmodMode::None).entries(map());
+        assert!(entries.iter().all(|e| e.lastmod.is_none()));
+    }
 }
@@ -0,0 +1,185 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Counters of the running crawl in the Prometheus text format.
+pub struct Metrics {
+    pub fetched: BTreeMap<u16, u64>,
+    pub errors: BTreeMap<&'static str, u64>,
+    pub retries: u64,
+    pub bytes: u64,
+    pub queue_size: i64,
+    pub sitemap_urls: usize,
+    pub success: bool,
+    started: Instant,
+    duration: Option<Duration>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            fetched: BTreeMap::new(),
+            errors: BTreeMap::new(),
+            retries: 0,
+            bytes: 0,
+            queue_size: 0,
+            sitemap_urls: 0,
+            success: false,
+            started: Instant::now(),
+            duration: None,
+        }
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    out.push_str(&format!("# HELP xmlsitemapper_{} {}\n# TYPE xmlsitemapper_{} {}\n", name, help, name, kind));
+}
+
+impl Metrics {
+    pub fn finish(&mut self) {
+        self.duration = Some(self.started.elapsed());
+        self.queue_size = 0;
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        family(&mut out, "fetched_urls_total", "counter", "Responses received, by HTTP status code.");
+        for (status, count) in self.fetched.iter() {
+            out.push_str(&format!("xmlsitemapper_fetched_urls_total{{status=\"{}\"}} {}\n", status, count));
+        }
+        family(&mut out, "errors_total", "counter", "Failed requests, by error class.");
+        for (class, count) in self.errors.iter() {
+            out.push_str(&format!("xmlsitemapper_errors_total{{class=\"{}\"}} {}\n", class, count));
+        }
+        family(&mut out, "retries_total", "counter", "Requests sent again after a retryable failure.");
+        out.push_str(&format!("xmlsitemapper_retries_total {}\n", self.retries));
+        family(&mut out, "downloaded_bytes_total", "counter", "Bytes of response bodies read.");
+        out.push_str(&format!("xmlsitemapper_downloaded_bytes_total {}\n", self.bytes));
+        family(&mut out, "queue_size", "gauge", "URLs waiting to be crawled.");
+        out.push_str(&format!("xmlsitemapper_queue_size {}\n", self.queue_size));
+        family(&mut out, "sitemap_urls", "gauge", "URLs written to the sitemap.");
+        out.push_str(&format!("xmlsitemapper_sitemap_urls {}\n", self.sitemap_urls));
+        family(&mut out, "crawl_duration_seconds", "gauge", "Duration of the crawl so far.");
+        let duration = self.duration.unwrap_or_else(|| self.started.elapsed());
+        out.push_str(&format!("xmlsitemapper_crawl_duration_seconds {:.3}\n", duration.as_secs_f64()));
+        family(&mut out, "last_run_success", "gauge", "1 if the sitemap was written successfully.");
+        out.push_str(&format!("xmlsitemapper_last_run_success {}\n", self.success as u8));
+        family(&mut out, "last_run_timestamp_seconds", "gauge", "Unix time the metrics were rendered.");
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        out.push_str(&format!("xmlsitemapper_last_run_timestamp_seconds {}\n", now.as_secs()));
+        out
+    }
+}
+
+/// Metrics shared between the crawler and the `/metrics` endpoint.
+#[derive(Clone, Default)]
+pub struct SharedMetrics(Arc<Mutex<Metrics>>);
+
+impl SharedMetrics {
+    pub fn new() -> SharedMetrics {
+        SharedMetrics::default()
+    }
+
+    pub fn update<F: FnOnce(&mut Metrics)>(&self, f: F) {
+        if let Ok(mut metrics) = self.0.lock() {
+            f(&mut metrics);
+        }
+    }
+
+    pub fn render(&self) -> String {
+        match self.0.lock() {
+            Ok(metrics) => metrics.render(),
+            Err(_) => String::new(),
+        }
+    }
+
+    /// Writes the textfile collector file through a temporary file, so that
+    /// node_exporter never reads a half written file.
+    pub fn write_textfile(&self, path: &str) -> io::Result<()> {
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, self.render())?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Serves `GET /metrics` on `addr` from a background thread until the process exits.
+    pub fn serve(&self, addr: &str) -> io::Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        let metrics = self.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = metrics.respond(stream);
+            }
+        });
+        Ok(())
+    }
+
+    fn respond(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut request = String::new();
+        BufReader::new(&stream).read_line(&mut request)?;
+        let path = request.split_whitespace().nth(1).unwrap_or("");
+        let (status, body) = if path == "/metrics" || path.starts_with("/metrics?") {
+            ("200 OK", self.render())
+        } else {
+            ("404 Not Found", String::from("Not found\n"))
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )?;
+        stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn render_counters() {
+        let metrics = SharedMetrics::new();
+        metrics.update(|m| {
+            *m.fetched.entry(200).or_default() += 3;
+            *m.errors.entry("timeout").or_default() += 1;
+            m.retries = 2;
+            m.queue_size = 5;
+            m.success = true;
+            m.finish();
+        });
+        let text = metrics.render();
+        assert!(text.contains("# TYPE xmlsitemapper_fetched_urls_total counter\n"));
+        assert!(text.contains("xmlsitemapper_fetched_urls_total{status=\"200\"} 3\n"));
+        assert!(text.contains("xmlsitemapper_errors_total{class=\"timeout\"} 1\n"));
+        assert!(text.contains("xmlsitemapper_retries_total 2\n"));
+        assert!(text.contains("xmlsitemapper_queue_size 0\n"));
+        assert!(text.contains("xmlsitemapper_last_run_success 1\n"));
+    }
+
+    fn get(metrics: &SharedMetrics, path: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        write!(client, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        metrics.respond(server).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn endpoint() {
+        let metrics = SharedMetrics::new();
+        let response = get(&metrics, "/metrics?name[]=x");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("xmlsitemapper_sitemap_urls 0\n"));
+        assert!(get(&metrics, "/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
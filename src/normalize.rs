@@ -0,0 +1,194 @@
+use crate::options::{Options, TrailingSlash};
+use reqwest::Url;
+use url::form_urlencoded;
+
+const INDEX_PAGES: [&str; 5] = ["index.html", "index.htm", "index.php", "index.asp", "index.aspx"];
+
+/// Brings URLs to one canonical form so the same page is never visited or mapped twice.
+pub struct Normalizer {
+    scheme: String,
+    host: Option<String>,
+    strip_params: Vec<String>,
+    sort_params: bool,
+    trailing_slash: TrailingSlash,
+    lowercase_paths: bool,
+    drop_fragments: bool,
+    collapse_index: bool,
+}
+
+impl Normalizer {
+    pub fn new(main_url: &Url, options: &Options) -> Normalizer {
+        Normalizer {
+            scheme: String::from(main_url.scheme()),
+            host: main_url.host_str().map(String::from),
+            strip_params: options.strip_params.clone(),
+            sort_params: options.sort_params,
+            trailing_slash: options.trailing_slash,
+            lowercase_paths: options.lowercase_paths,
+            drop_fragments: options.drop_fragments,
+            collapse_index: options.collapse_index,
+        }
+    }
+
+    /// Parameters are matched by name, a trailing `*` matches any suffix (`utm_*`).
+    fn is_stripped(&self, name: &str) -> bool {
+        self.strip_params.iter().any(|p| match p.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == p,
+        })
+    }
+
+    /// Drops stripped parameters and sorts the rest, moving the parameters as they are written
+    /// so that their encoding never changes (`?foo` stays `?foo`, `%20` stays `%20`).
+    fn normalize_query(&self, url: &mut Url) {
+        let query = match url.query() {
+            Some(query) => query,
+            None => {
+                return;
+            }
+        };
+        let mut params: Vec<&str> = query
+            .split('&')
+            .filter(|p| {
+                let name = form_urlencoded::parse(p.as_bytes()).next().map(|(name, _)| name);
+                !name.is_some_and(|name| self.is_stripped(&name))
+            })
+            .collect();
+        if self.sort_params {
+            params.sort_unstable();
+        }
+        let normalized = params.join("&");
+        if normalized == query {
+            return;
+        }
+        if params.iter().all(|p| p.is_empty()) {
+            url.set_query(None);
+        } else {
+            url.set_query(Some(&normalized));
+        }
+    }
+
+    fn normalize_path(&self, url: &mut Url) {
+        let mut path = String::from(url.path());
+        if self.lowercase_paths {
+            path = path.to_lowercase();
+        }
+        if self.collapse_index {
+            for index in INDEX_PAGES.iter() {
+                if path.ends_with(&format!("/{}", index)) {
+                    path.truncate(path.len() - index.len());
+                    break;
+                }
+            }
+        }
+        match self.trailing_slash {
+            TrailingSlash::Keep => {}
+            TrailingSlash::Add => {
+                let last = path.rsplit('/').next().unwrap_or("");
+                if !last.is_empty() && !last.contains('.') {
+                    path.push('/');
+                }
+            }
+            TrailingSlash::Remove => {
+                while path.len() > 1 && path.ends_with('/') {
+                    path.pop();
+                }
+            }
+        }
+        url.set_path(&path);
+    }
+
+    /// Only urls on the host of the site root get its scheme, other hosts in scope keep their own.
+    pub fn normalize(&self, mut url: Url) -> Option<Url> {
+        if url.host_str() == self.host.as_deref() {
+            url.set_scheme(&self.scheme).ok()?;
+        }
+        if self.drop_fragments {
+            url.set_fragment(None);
+        }
+        self.normalize_query(&mut url);
+        self.normalize_path(&mut url);
+        Some(url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(normalizer: &Normalizer, url: &str) -> String {
+        String::from(normalizer.normalize(Url::parse(url).unwrap()).unwrap().as_str())
+    }
+
+    #[test]
+    fn scheme_of_the_root_only_on_its_host() {
+        let normalizer = Normalizer::new(&Url::parse("https://foo.bar/").unwrap(), &Options::default());
+        assert_eq!(normalize(&normalizer, "http://foo.bar/a"), "https://foo.bar/a");
+        assert_eq!(normalize(&normalizer, "http://shop.foo.bar/a"), "http://shop.foo.bar/a");
+    }
+
+    fn normalizer(options: Options) -> Normalizer {
+        Normalizer::new(&Url::parse("https://foo.bar/").unwrap(), &options)
+    }
+
+    #[test]
+    fn strip_params_with_wildcards() {
+        let normalizer = normalizer(Options {
+            strip_params: vec![String::from("utm_*"), String::from("fbclid")],
+            ..Options::default()
+        });
+        assert_eq!(normalize(&normalizer, "https://foo.bar/a?utm_source=x&b=2&fbclid=1&utm=3"), "https://foo.bar/a?b=2&utm=3");
+        assert_eq!(normalize(&normalizer, "https://foo.bar/a?utm_source=x&utm_medium=y"), "https://foo.bar/a");
+    }
+
+    #[test]
+    fn query_keeps_its_encoding() {
+        let sorted = normalizer(Options::default());
+        assert_eq!(normalize(&sorted, "https://foo.bar/?foo&x=a%20b"), "https://foo.bar/?foo&x=a%20b");
+        assert_eq!(normalize(&sorted, "https://foo.bar/?x=a+b&foo"), "https://foo.bar/?foo&x=a+b");
+        assert_eq!(normalize(&sorted, "https://foo.bar/?b=2&a=1"), "https://foo.bar/?a=1&b=2");
+        let unsorted = normalizer(Options {
+            sort_params: false,
+            ..Options::default()
+        });
+        assert_eq!(normalize(&unsorted, "https://foo.bar/?b=2&a=1"), "https://foo.bar/?b=2&a=1");
+    }
+
+    #[test]
+    fn trailing_slash() {
+        let add = normalizer(Options {
+            trailing_slash: TrailingSlash::Add,
+            ..Options::default()
+        });
+        assert_eq!(normalize(&add, "https://foo.bar/docs"), "https://foo.bar/docs/");
+        assert_eq!(normalize(&add, "https://foo.bar/docs/a.html"), "https://foo.bar/docs/a.html");
+        let remove = normalizer(Options {
+            trailing_slash: TrailingSlash::Remove,
+            ..Options::default()
+        });
+        assert_eq!(normalize(&remove, "https://foo.bar/docs//"), "https://foo.bar/docs");
+        assert_eq!(normalize(&remove, "https://foo.bar/"), "https://foo.bar/");
+        let keep = normalizer(Options::default());
+        assert_eq!(normalize(&keep, "https://foo.bar/docs/"), "https://foo.bar/docs/");
+    }
+
+    #[test]
+    fn collapse_index() {
+        let normalizer = normalizer(Options {
+            collapse_index: true,
+            ..Options::default()
+        });
+        assert_eq!(normalize(&normalizer, "https://foo.bar/docs/index.html"), "https://foo.bar/docs/");
+        assert_eq!(normalize(&normalizer, "https://foo.bar/index.php?a=1"), "https://foo.bar/?a=1");
+        assert_eq!(normalize(&normalizer, "https://foo.bar/docs/myindex.html"), "https://foo.bar/docs/myindex.html");
+    }
+
+    #[test]
+    fn fragments_and_lowercase_paths() {
+        let normalizer = normalizer(Options {
+            lowercase_paths: true,
+            ..Options::default()
+        });
+        assert_eq!(normalize(&normalizer, "https://foo.bar/Docs/A.html#top"), "https://foo.bar/docs/a.html");
+    }
+}
@@ -0,0 +1,948 @@
+use crate::auth;
+use regex::Regex;
+use reqwest::Url;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PriorityKind {
+    Segments,
+    Inlinks,
+    PageRank,
+}
+
+impl FromStr for PriorityKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "segments" => Ok(PriorityKind::Segments),
+            "inlinks" => Ok(PriorityKind::Inlinks),
+            "pagerank" => Ok(PriorityKind::PageRank),
+            _ => Err(format!(
+                "Unknown priority strategy '{}', expected segments, inlinks or pagerank.",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GraphFormat {
+    Dot,
+    GraphMl,
+    Json,
+}
+
+impl GraphFormat {
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "link_graph.dot",
+            GraphFormat::GraphMl => "link_graph.graphml",
+            GraphFormat::Json => "link_graph.json",
+        }
+    }
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "graphml" => Ok(GraphFormat::GraphMl),
+            "json" => Ok(GraphFormat::Json),
+            _ => Err(format!(
+                "Unknown link graph format '{}', expected dot, graphml or json.",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ScopeKind {
+    Host,
+    Subdomains,
+    Hosts,
+}
+
+impl FromStr for ScopeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "host" => Ok(ScopeKind::Host),
+            "subdomains" => Ok(ScopeKind::Subdomains),
+            "hosts" => Ok(ScopeKind::Hosts),
+            _ => Err(format!(
+                "Unknown scope '{}', expected host, subdomains or hosts.",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TrailingSlash {
+    Keep,
+    Add,
+    Remove,
+}
+
+impl FromStr for TrailingSlash {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(TrailingSlash::Keep),
+            "add" => Ok(TrailingSlash::Add),
+            "remove" => Ok(TrailingSlash::Remove),
+            _ => Err(format!(
+                "Unknown trailing slash mode '{}', expected keep, add or remove.",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DedupeMode {
+    Off,
+    Exact,
+    Similar,
+}
+
+impl FromStr for DedupeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(DedupeMode::Off),
+            "exact" => Ok(DedupeMode::Exact),
+            "similar" => Ok(DedupeMode::Similar),
+            _ => Err(format!(
+                "Unknown duplicate detection mode '{}', expected off, exact or similar.",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DedupePreference {
+    Shortest,
+    FewestParams,
+    FirstCrawled,
+    HighestPriority,
+}
+
+impl FromStr for DedupePreference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shortest" => Ok(DedupePreference::Shortest),
+            "fewest_params" => Ok(DedupePreference::FewestParams),
+            "first_crawled" => Ok(DedupePreference::FirstCrawled),
+            "highest_priority" => Ok(DedupePreference::HighestPriority),
+            _ => Err(format!(
+                "Unknown duplicate preference '{}', expected shortest, fewest_params, first_crawled or highest_priority.",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DocumentKind {
+    Pdf,
+    Text,
+    Xml,
+}
+
+impl DocumentKind {
+    pub fn of_mime(mime: &str) -> Option<DocumentKind> {
+        match mime {
+            "application/pdf" => Some(DocumentKind::Pdf),
+            "text/plain" => Some(DocumentKind::Text),
+            "text/xml" | "application/xml" => Some(DocumentKind::Xml),
+            m if m.ends_with("+xml") => Some(DocumentKind::Xml),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for DocumentKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pdf" => Ok(DocumentKind::Pdf),
+            "text" => Ok(DocumentKind::Text),
+            "xml" => Ok(DocumentKind::Xml),
+            _ => Err(format!(
+                "Unknown document kind '{}', expected pdf, text or xml.",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LinkSource {
+    Anchor,
+    Area,
+    Link,
+    Iframe,
+    Form,
+    MetaRefresh,
+}
+
+impl FromStr for LinkSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "a" => Ok(LinkSource::Anchor),
+            "area" => Ok(LinkSource::Area),
+            "link" => Ok(LinkSource::Link),
+            "iframe" => Ok(LinkSource::Iframe),
+            "form" => Ok(LinkSource::Form),
+            "meta_refresh" => Ok(LinkSource::MetaRefresh),
+            _ => Err(format!(
+                "Unknown link source '{}', expected a, area, link, iframe, form or meta_refresh.",
+                s
+            )),
+        }
+    }
+}
+
+fn parse_strings(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|v| String::from(v.trim()))
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+/// Parses rules written as `from -> to`.
+fn parse_rule(key: &str, value: &str) -> Result<(String, String), String> {
+    match value.split_once("->") {
+        Some((from, to)) => Ok((String::from(from.trim()), String::from(to.trim()))),
+        None => Err(format!("Option '{}' expects 'from -> to', got '{}'.", key, value)),
+    }
+}
+
+fn parse_list<T: FromStr<Err = String>>(value: &str) -> Result<Vec<T>, String> {
+    value
+        .split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse())
+        .collect()
+}
+
+fn parse_number<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Option '{}' expects a number, got '{}'.", key, value))
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "1" => Ok(true),
+        "false" | "no" | "0" => Ok(false),
+        _ => Err(format!("Option '{}' expects true or false, got '{}'.", key, value)),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Xml,
+    Text,
+    Csv,
+    Json,
+    Rss,
+    Atom,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "xml" => Ok(OutputFormat::Xml),
+            "txt" => Ok(OutputFormat::Text),
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "rss" => Ok(OutputFormat::Rss),
+            "atom" => Ok(OutputFormat::Atom),
+            _ => Err(format!(
+                "Unknown output format '{}', expected xml, txt, csv, json, rss or atom.",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SitemapOrder {
+    Url,
+    Priority,
+    Discovery,
+}
+
+impl FromStr for SitemapOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "url" => Ok(SitemapOrder::Url),
+            "priority" => Ok(SitemapOrder::Priority),
+            "discovery" => Ok(SitemapOrder::Discovery),
+            _ => Err(format!(
+                "Unknown sitemap order '{}', expected url, priority or discovery.",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LastmodMode {
+    Crawl,
+    LastModified,
+    None,
+}
+
+impl FromStr for LastmodMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "crawl" => Ok(LastmodMode::Crawl),
+            "last_modified" => Ok(LastmodMode::LastModified),
+            "none" => Ok(LastmodMode::None),
+            _ => Err(format!(
+                "Unknown lastmod mode '{}', expected crawl, last_modified or none.",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            _ => Err(format!(
+                "Unknown log level '{}', expected error, warn, info or debug.",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unknown log format '{}', expected text or json.", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LogMode {
+    Truncate,
+    Append,
+    Rotate,
+}
+
+impl FromStr for LogMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "truncate" => Ok(LogMode::Truncate),
+            "append" => Ok(LogMode::Append),
+            "rotate" => Ok(LogMode::Rotate),
+            _ => Err(format!(
+                "Unknown log mode '{}', expected truncate, append or rotate.",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Options {
+    pub priority_strategy: PriorityKind,
+    pub graph_formats: Vec<GraphFormat>,
+    pub graph_collapse_depth: Option<usize>,
+    pub merge_orphans: bool,
+    pub seed_sitemaps: Vec<String>,
+    pub seed_robots: bool,
+    pub respect_robots: bool,
+    pub respect_noindex: bool,
+    pub seed_urls_file: Option<String>,
+    pub scope: ScopeKind,
+    pub scope_hosts: Vec<String>,
+    pub scope_path_prefix: Option<String>,
+    pub sitemap_per_host: bool,
+    pub rewrite_hosts: Vec<(String, String)>,
+    pub rewrite_paths: Vec<(String, String)>,
+    pub strip_params: Vec<String>,
+    pub sort_params: bool,
+    pub trailing_slash: TrailingSlash,
+    pub lowercase_paths: bool,
+    pub drop_fragments: bool,
+    pub collapse_index: bool,
+    pub dedupe: DedupeMode,
+    pub dedupe_threshold: u32,
+    pub dedupe_min_words: usize,
+    pub dedupe_prefer: DedupePreference,
+    pub include_types: Vec<String>,
+    pub head_only_types: Vec<String>,
+    pub skip_types: Vec<String>,
+    pub document_links: Vec<DocumentKind>,
+    pub link_sources: Vec<LinkSource>,
+    pub link_attributes: Vec<String>,
+    pub retries: u32,
+    pub retry_base_delay: u64,
+    pub retry_max_delay: u64,
+    pub retry_after_max: u64,
+    pub adaptive_delay: bool,
+    pub connect_timeout: u64,
+    pub timeout: u64,
+    pub user_agent: String,
+    pub headers: Vec<(String, String)>,
+    pub proxy: Option<String>,
+    pub pool_max_idle: usize,
+    pub max_response_size: Option<u64>,
+    pub cookie_jar: bool,
+    pub cookies_file: Option<String>,
+    pub basic_auth_user: Option<String>,
+    pub basic_auth_password: Option<String>,
+    pub bearer_token: Option<String>,
+    pub login_url: Option<String>,
+    pub login_fields: Vec<(String, String)>,
+    pub ca_files: Vec<String>,
+    pub ca_only: bool,
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+    pub client_cert_password: Option<String>,
+    pub insecure: bool,
+    pub log_file: String,
+    pub log_level: LogLevel,
+    pub log_format: LogFormat,
+    pub log_mode: LogMode,
+    pub log_keep: usize,
+    pub stats_json: bool,
+    pub metrics_file: Option<String>,
+    pub metrics_listen: Option<String>,
+    pub diff_previous: bool,
+    pub sitemap_order: SitemapOrder,
+    pub lastmod: LastmodMode,
+    pub output_formats: Vec<OutputFormat>,
+    pub feed_size: usize,
+    pub feed_author: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            priority_strategy: PriorityKind::Segments,
+            graph_formats: Vec::new(),
+            graph_collapse_depth: None,
+            merge_orphans: false,
+            seed_sitemaps: Vec::new(),
+            seed_robots: false,
+            respect_robots: false,
+            respect_noindex: false,
+            seed_urls_file: None,
+            scope: ScopeKind::Host,
+            scope_hosts: Vec::new(),
+            scope_path_prefix: None,
+            sitemap_per_host: false,
+            rewrite_hosts: Vec::new(),
+            rewrite_paths: Vec::new(),
+            strip_params: Vec::new(),
+            sort_params: true,
+            trailing_slash: TrailingSlash::Keep,
+            lowercase_paths: false,
+            drop_fragments: true,
+            collapse_index: false,
+            dedupe: DedupeMode::Off,
+            dedupe_threshold: 3,
+            dedupe_min_words: 20,
+            dedupe_prefer: DedupePreference::Shortest,
+            include_types: vec![String::from("*")],
+            head_only_types: Vec::new(),
+            skip_types: Vec::new(),
+            document_links: Vec::new(),
+            link_sources: vec![
+                LinkSource::Anchor,
+                LinkSource::Area,
+                LinkSource::Link,
+                LinkSource::Iframe,
+                LinkSource::MetaRefresh,
+            ],
+            link_attributes: Vec::new(),
+            retries: 3,
+            retry_base_delay: 500,
+            retry_max_delay: 30000,
+            retry_after_max: 600000,
+            adaptive_delay: true,
+            connect_timeout: 10,
+            timeout: 30,
+            user_agent: format!("XmlSiteMapper-rs/{}", env!("CARGO_PKG_VERSION")),
+            headers: Vec::new(),
+            proxy: None,
+            pool_max_idle: 8,
+            max_response_size: Some(10 * 1024 * 1024),
+            cookie_jar: false,
+            cookies_file: None,
+            basic_auth_user: None,
+            basic_auth_password: None,
+            bearer_token: None,
+            login_url: None,
+            login_fields: Vec::new(),
+            ca_files: Vec::new(),
+            ca_only: false,
+            client_cert: None,
+            client_key: None,
+            client_cert_password: None,
+            insecure: false,
+            log_file: String::from("XmlSiteMapper-rs.log"),
+            log_level: LogLevel::Info,
+            log_format: LogFormat::Text,
+            log_mode: LogMode::Rotate,
+            log_keep: 5,
+            stats_json: true,
+            metrics_file: None,
+            metrics_listen: None,
+            diff_previous: false,
+            sitemap_order: SitemapOrder::Url,
+            lastmod: LastmodMode::LastModified,
+            output_formats: vec![OutputFormat::Xml],
+            feed_size: 20,
+            feed_author: None,
+        }
+    }
+}
+
+impl Options {
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "priority_strategy" => self.priority_strategy = value.parse()?,
+            "graph_formats" => self.graph_formats = parse_list(value)?,
+            "graph_collapse_depth" => self.graph_collapse_depth = Some(parse_number(key, value)?),
+            "merge_orphans" => self.merge_orphans = parse_bool(key, value)?,
+            "seed_sitemaps" => self.seed_sitemaps = parse_strings(value),
+            "seed_robots" => self.seed_robots = parse_bool(key, value)?,
+            "respect_robots" => self.respect_robots = parse_bool(key, value)?,
+            "respect_noindex" => self.respect_noindex = parse_bool(key, value)?,
+            "seed_urls_file" => self.seed_urls_file = Some(String::from(value)),
+            "scope" => self.scope = value.parse()?,
+            "scope_hosts" => self.scope_hosts = parse_strings(value),
+            "scope_path_prefix" => self.scope_path_prefix = Some(String::from(value)),
+            "sitemap_per_host" => self.sitemap_per_host = parse_bool(key, value)?,
+            "rewrite_host" => {
+                let (from, to) = parse_rule(key, value)?;
+                if Url::parse(&format!("http://{}/", from)).is_err() {
+                    return Err(format!("Option '{}' has an invalid host '{}'.", key, from));
+                }
+                if Url::parse(&to).is_err() {
+                    return Err(format!("Option '{}' has an invalid target '{}', it should look like https://foo.bar.", key, to));
+                }
+                self.rewrite_hosts.push((from, to));
+            }
+            "rewrite_path" => {
+                let (re, replacement) = parse_rule(key, value)?;
+                if let Err(e) = Regex::new(&re) {
+                    return Err(format!("Option '{}' has an invalid regex '{}': {}", key, re, e));
+                }
+                self.rewrite_paths.push((re, replacement));
+            }
+            "strip_params" => self.strip_params = parse_strings(value),
+            "sort_params" => self.sort_params = parse_bool(key, value)?,
+            "trailing_slash" => self.trailing_slash = value.parse()?,
+            "lowercase_paths" => self.lowercase_paths = parse_bool(key, value)?,
+            "drop_fragments" => self.drop_fragments = parse_bool(key, value)?,
+            "collapse_index" => self.collapse_index = parse_bool(key, value)?,
+            "dedupe" => self.dedupe = value.parse()?,
+            "dedupe_threshold" => self.dedupe_threshold = parse_number(key, value)?,
+            "dedupe_min_words" => self.dedupe_min_words = parse_number(key, value)?,
+            "dedupe_prefer" => self.dedupe_prefer = value.parse()?,
+            "include_types" => self.include_types = parse_strings(value),
+            "head_only_types" => self.head_only_types = parse_strings(value),
+            "skip_types" => self.skip_types = parse_strings(value),
+            "document_links" => self.document_links = parse_list(value)?,
+            "link_sources" => self.link_sources = parse_list(value)?,
+            "link_attributes" => self.link_attributes = parse_strings(value),
+            "retries" => self.retries = parse_number(key, value)?,
+            "retry_base_delay" => self.retry_base_delay = parse_number(key, value)?,
+            "retry_max_delay" => self.retry_max_delay = parse_number(key, value)?,
+            "retry_after_max" => self.retry_after_max = parse_number(key, value)?,
+            "adaptive_delay" => self.adaptive_delay = parse_bool(key, value)?,
+            "connect_timeout" => self.connect_timeout = parse_number(key, value)?,
+            "timeout" => self.timeout = parse_number(key, value)?,
+            "user_agent" => self.user_agent = String::from(value),
+            "header" => match value.split_once(':') {
+                Some((name, header)) => self.headers.push((String::from(name.trim()), String::from(header.trim()))),
+                None => return Err(format!("Option '{}' expects 'Name: value', got '{}'.", key, value)),
+            },
+            "proxy" => self.proxy = Some(String::from(value)),
+            "pool_max_idle" => self.pool_max_idle = parse_number(key, value)?,
+            "max_response_size" => {
+                let size: u64 = parse_number(key, value)?;
+                self.max_response_size = if size == 0 { None } else { Some(size) };
+            }
+            "cookie_jar" => self.cookie_jar = parse_bool(key, value)?,
+            "cookies_file" => self.cookies_file = Some(String::from(value)),
+            "basic_auth_user" => self.basic_auth_user = Some(String::from(value)),
+            "basic_auth_password" => self.basic_auth_password = Some(auth::secret_from_env(key, value)?),
+            "bearer_token" => self.bearer_token = Some(auth::secret_from_env(key, value)?),
+            "login_url" => {
+                if Url::parse(value).is_err() {
+                    return Err(format!("Option '{}' expects an absolute URL, got '{}'.", key, value));
+                }
+                self.login_url = Some(String::from(value));
+            }
+            "login_field" => match value.split_once('=') {
+                Some((name, field)) => {
+                    let field = field.trim();
+                    let field = if field.starts_with('$') || auth::is_secret_field(name.trim()) {
+                        auth::secret_from_env(key, field)?
+                    } else {
+                        String::from(field)
+                    };
+                    self.login_fields.push((String::from(name.trim()), field));
+                }
+                None => return Err(format!("Option '{}' expects 'name = value', got '{}'.", key, value)),
+            },
+            "ca_file" => self.ca_files.push(String::from(value)),
+            "ca_only" => self.ca_only = parse_bool(key, value)?,
+            "client_cert" => self.client_cert = Some(String::from(value)),
+            "client_key" => self.client_key = Some(String::from(value)),
+            "client_cert_password" => self.client_cert_password = Some(auth::secret_from_env(key, value)?),
+            "insecure" => self.insecure = parse_bool(key, value)?,
+            "log_file" => self.log_file = String::from(value),
+            "log_level" => self.log_level = value.parse()?,
+            "log_format" => self.log_format = value.parse()?,
+            "log_mode" => self.log_mode = value.parse()?,
+            "log_keep" => self.log_keep = parse_number(key, value)?,
+            "stats_json" => self.stats_json = parse_bool(key, value)?,
+            "metrics_file" => self.metrics_file = Some(String::from(value)),
+            "metrics_listen" => self.metrics_listen = Some(String::from(value)),
+            "diff_previous" => self.diff_previous = parse_bool(key, value)?,
+            "sitemap_order" => self.sitemap_order = value.parse()?,
+            "lastmod" => self.lastmod = value.parse()?,
+            "output_formats" => {
+                self.output_formats = parse_list(value)?;
+                if self.output_formats.is_empty() {
+                    return Err(format!("Option '{}' needs at least one format.", key));
+                }
+            }
+            "feed_size" => self.feed_size = parse_number(key, value)?,
+            "feed_author" => self.feed_author = Some(String::from(value)),
+            _ => return Err(format!("Unknown option '{}' in options.cfg.", key)),
+        }
+        Ok(())
+    }
+}
+
+pub const OPTIONS_TEMPLATE: &str = "# Each option is written as 'key = value'. Lines starting with # are ignored.
+# Uncomment an option and change its value to override the default.
+#
+# How the base <priority> of each URL is computed before change_prio.cfg is applied:
+#   segments - 1.0 minus 0.1 for every path segment and query parameter
+#   inlinks  - number of crawled pages linking to the URL, scaled to 0.1..1.0
+#   pagerank - PageRank score over the crawled link graph, scaled to 0.1..1.0
+# priority_strategy = segments
+#
+# Comma separated list of formats (dot, graphml, json) to export the crawled link graph in.
+# Files are named link_graph.<format> and are written next to sitemap.xml.
+# graph_formats = dot, graphml, json
+#
+# Collapse graph nodes to their first N path segments, so /blog/a and /blog/b become /blog/
+# with a weighted edge. Leave unset to keep one node per URL.
+# graph_collapse_depth = 1
+#
+# When a list of known URLs is given with -k, add the known URLs which the crawl did not reach
+# to the sitemap, provided they answer with 200 OK and are not excluded by disallow.cfg, robots,
+# include_types or noindex. Pages the crawl reached but left out are never merged.
+# merge_orphans = false
+#
+# Start the crawl not only from the site root, but also from every URL of these sitemaps.
+# Comma separated list of URLs or local files; sitemap indexes and gzipped files are supported.
+# seed_sitemaps = https://foo.bar/sitemap.xml, old_sitemap.xml.gz
+#
+# Also seed the crawl from the sitemaps listed in 'Sitemap:' lines of the site's robots.txt.
+# seed_robots = false
+#
+# Do not crawl URLs disallowed by the robots.txt of their site (rules for the user_agent
+# product token, otherwise the * rules), and leave pages marked noindex by
+# <meta name=robots> or X-Robots-Tag out of the sitemap. Links on noindex pages are followed.
+# The site root from site.cfg is always crawled, even when robots.txt disallows it (as staging
+# servers with Disallow: / do).
+# respect_robots = false
+# respect_noindex = false
+#
+# Seed the crawl from a local file with URLs (one per line, CSV with URLs in the first column,
+# or a sitemap).
+# seed_urls_file = urls.txt
+#
+# Which hosts are crawled:
+#   host       - only the host of the site root from site.cfg
+#   subdomains - the host of the site root, its apex domain (without www.) and all their subdomains
+#   hosts      - the host of the site root and the hosts listed in scope_hosts
+# Hosts are compared together with their scheme and port, so https://foo.bar/, http://foo.bar/
+# and https://foo.bar:8443/ are different sites. scope_hosts entries without a scheme use
+# the scheme of the site root. IP addresses and localhost never have subdomains.
+# scope = host
+# scope_hosts = blog.foo.bar, http://shop.foo.bar, 10.0.0.5:8443, [::1]:3000
+#
+# Only crawl URLs whose path starts with this prefix. The site root in site.cfg must start with it too.
+# scope_path_prefix = /docs/
+#
+# Write one sitemap_<host>.xml (sitemap_<host>_<port>.xml for non-default ports) per crawled host instead of a single combined sitemap.xml.
+# sitemap_per_host = false
+#
+# Rewrite crawled URLs before they are written, e.g. to crawl staging but publish production URLs.
+# Both options may be repeated, every line adds a rule. Host rules replace the scheme, host and port
+# of matching URLs; path rules replace the first match of the regex in the path ($1 etc. refer to
+# capture groups) and are applied in order after host rules.
+# rewrite_host = staging.foo.internal:8080 -> https://www.foo.bar
+# rewrite_path = ^/staging/(.*) -> /$1
+#
+# URL normalization, applied to every URL before it is visited and written.
+# Query parameters to remove; a trailing * matches any suffix.
+# strip_params = utm_*, fbclid, gclid, sessionid, PHPSESSID
+# Sort the remaining query parameters, so ?a=1&b=2 and ?b=2&a=1 are the same page. Parameters
+# keep their encoding, only their order changes.
+# sort_params = true
+# keep, add (only to paths without a file extension) or remove the trailing slash.
+# trailing_slash = keep
+# lowercase_paths = false
+# drop_fragments = true
+# Turn /docs/index.html (also .htm, .php, .asp, .aspx) into /docs/.
+# collapse_index = false
+#
+# Detect HTML pages with the same text and keep only one of them in the sitemap:
+#   off     - keep every page
+#   exact   - group pages with identical text (whitespace, scripts and styles are ignored)
+#   similar - also group near-duplicates whose SimHash differs in at most dedupe_threshold bits (of 64)
+# The dropped pages are listed in duplicates.txt next to sitemap.xml.
+# dedupe = off
+# dedupe_threshold = 3
+# Pages with fewer words of text are never treated as duplicates.
+# dedupe_min_words = 20
+# Which page of a group is kept: shortest, fewest_params, first_crawled or highest_priority URL.
+# dedupe_prefer = shortest
+#
+# Content type policy. Types are written as text/html, as image/* for a whole group, or as *.
+# Responses of these types are added to the sitemap (links are still read from any HTML page):
+# include_types = *
+# URLs whose extension suggests one of these types are requested with HEAD, so their body is
+# never downloaded:
+# head_only_types = image/*, video/*, application/zip
+# URLs of these types are not requested at all when their extension gives them away, and are
+# never added to the sitemap:
+# skip_types = font/*, text/css, application/javascript
+#
+# Besides HTML pages, also look for links in these documents: pdf (link annotations and URLs in
+# the text), text (URLs in text/plain files) and xml (URLs in XML files). Links leading to the
+# mapped site are crawled like the ones from <a href>.
+# document_links = pdf, text, xml
+#
+# Where links are looked for in HTML pages:
+#   a            - <a href>
+#   area         - <area href> of image maps
+#   link         - <link href> with rel next, prev or alternate
+#   iframe       - <iframe src>
+#   form         - action of <form> elements submitted with GET
+#   meta_refresh - target of <meta http-equiv=refresh>
+# link_sources = a, area, link, iframe, meta_refresh
+#
+# Additional attributes holding URLs on any element, e.g. used by scripts.
+# link_attributes = data-href, data-url
+#
+# Connection errors, timeouts, 429 and 5xx responses are retried this many times. The pause
+# before a retry starts at retry_base_delay ms and doubles every time (with some random jitter),
+# but never exceeds retry_max_delay ms. A Retry-After header sent by the server takes precedence
+# and is waited for in full, up to retry_after_max ms (10 minutes); only servers asking for an even
+# longer pause get the URL given up. 0 waits as long as the server asks.
+# retries = 3
+# retry_base_delay = 500
+# retry_max_delay = 30000
+# retry_after_max = 600000
+# After 429 or 503 responses, wait longer between all requests and speed up again gradually.
+# adaptive_delay = true
+#
+# HTTP client. Timeouts are in seconds, 0 disables them; timeout covers the whole request
+# including reading the body.
+# connect_timeout = 10
+# timeout = 30
+# user_agent = XmlSiteMapper-rs/<version>
+# Extra request header, may be repeated.
+# header = Accept-Language: en
+# HTTP, HTTPS or SOCKS5 proxy for all requests.
+# proxy = socks5://127.0.0.1:1080
+# Idle connections kept open per host for reuse.
+# pool_max_idle = 8
+# Bodies longer than this many bytes are not downloaded (0 means no limit).
+# max_response_size = 10485760
+#
+# Authentication. Passwords and tokens are never written here: give the name of an environment
+# variable holding them instead, e.g. $MAPPER_PASSWORD. Credentials are only sent to URLs
+# inside the crawl scope.
+# basic_auth_user = alice
+# basic_auth_password = $MAPPER_PASSWORD
+# bearer_token = $MAPPER_TOKEN
+#
+# Keep cookies set by the site between requests. Turned on automatically by cookies_file and
+# login_url.
+# cookie_jar = false
+# Cookies to start with, in the Netscape cookies.txt format exported by browsers and curl.
+# cookies_file = cookies.txt
+#
+# Log in by posting a form before the crawl starts; the session cookie is kept for the crawl.
+# login_field may be repeated, values starting with $ are read from environment variables.
+# Fields named like a password or token (pass, pwd, secret, token) only accept $VARIABLE values.
+# login_url = https://foo.bar/login
+# login_field = username=alice
+# login_field = password=$MAPPER_PASSWORD
+#
+# TLS. Extra trusted certificate authorities, PEM bundle or DER, may be repeated. When
+# ca_only is true the system roots are not trusted anymore.
+# ca_file = internal-ca.pem
+# ca_only = false
+# Client certificate for mutual TLS: a PEM certificate with its PKCS#8 PEM key, or a PKCS#12
+# archive (.p12) whose password is read from an environment variable.
+# client_cert = client.pem
+# client_key = client.key
+# client_cert_password = $MAPPER_CERT_PASSWORD
+# Accept invalid and self-signed certificates. Only for throwaway environments, never for
+# anything reachable from the internet.
+# insecure = false
+#
+# Crawl log. Levels are error, warn, info and debug. The json format writes one JSON object per
+# line, with a fetch event for every requested URL (url, status, content type, bytes, duration,
+# depth, referrer, source of the link and decision).
+# log_file = XmlSiteMapper-rs.log
+# log_level = info
+# log_format = text
+# What happens to the log of the previous run: truncate, append, or rotate it to
+# XmlSiteMapper-rs.log.1, .2 and so on, keeping log_keep old logs.
+# log_mode = rotate
+# log_keep = 5
+#
+# Write the crawl statistics printed at the end of a run to stats.json next to sitemap.xml.
+# stats_json = true
+#
+# Prometheus metrics. metrics_file is written at the end of the run for the node_exporter
+# textfile collector, metrics_listen serves /metrics on the given address while crawling.
+# metrics_file = /var/lib/node_exporter/textfile_collector/xmlsitemapper.prom
+# metrics_listen = 127.0.0.1:9898
+#
+# Compare the new sitemap with the one left by the previous run before overwriting it, and
+# write the changes to sitemap_diff.json.
+# diff_previous = false
+#
+# Order of the urls in the sitemap: url (alphabetical), priority (highest first) or discovery
+# (the order the crawl found them in).
+# sitemap_order = url
+# <lastmod> value: last_modified (the Last-Modified header, left out when the server does not
+# send one), crawl (start time of the crawl) or none. With last_modified or none identical
+# crawls produce byte-identical sitemaps.
+# lastmod = last_modified
+#
+# Files written from the crawl: xml (sitemap.xml), txt (plain text sitemap, one url per line),
+# csv (url, priority, lastmod and status), json, rss and atom (feeds of the most recently
+# modified pages, feed_size entries). The feeds use the Last-Modified header whatever lastmod
+# is set to and name feed_author, by default the host of the site, as the author.
+# output_formats = xml
+# feed_size = 20
+# feed_author = Example Inc.
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every `# key = value` example in the template is an option `set` accepts.
+    #[test]
+    fn template_examples_parse() {
+        for name in ["MAPPER_PASSWORD", "MAPPER_TOKEN", "MAPPER_CERT_PASSWORD"].iter() {
+            std::env::set_var(name, "secret");
+        }
+        let mut options = Options::default();
+        let mut failed = Vec::new();
+        for line in OPTIONS_TEMPLATE.lines() {
+            let line = line.trim_start_matches('#').trim();
+            let (key, value) = match line.split_once(" = ") {
+                Some((key, value)) if key.chars().all(|c| c.is_ascii_lowercase() || c == '_') => (key, value),
+                _ => continue,
+            };
+            if let Err(e) = options.set(key, value) {
+                failed.push(e);
+            }
+        }
+        assert!(failed.is_empty(), "{:?}", failed);
+    }
+
+    #[test]
+    fn values_and_errors() {
+        let mut options = Options::default();
+        options.set("graph_formats", "dot, ,json").unwrap();
+        assert_eq!(options.graph_formats.len(), 2);
+        options.set("header", "X-Env: staging").unwrap();
+        assert_eq!(options.headers, vec![(String::from("X-Env"), String::from("staging"))]);
+        options.set("max_response_size", "0").unwrap();
+        assert_eq!(options.max_response_size, None);
+        options.set("rewrite_host", "stage.foo.bar:8080 -> https://foo.bar").unwrap();
+        assert_eq!(options.rewrite_hosts[0].0, "stage.foo.bar:8080");
+        assert_eq!(options.set("retries", "many"), Err(String::from("Option 'retries' expects a number, got 'many'.")));
+        assert_eq!(options.set("insecure", "on"), Err(String::from("Option 'insecure' expects true or false, got 'on'.")));
+        assert!(options.set("rewrite_host", "foo.bar").is_err());
+        assert!(options.set("rewrite_path", "([a-z -> x").is_err());
+        assert!(options.set("output_formats", " ").is_err());
+        assert!(options.set("graph_formats", "dot, svg").is_err());
+        assert_eq!(options.set("colour", "1"), Err(String::from("Unknown option 'colour' in options.cfg.")));
+    }
+}
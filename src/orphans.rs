@@ -0,0 +1,76 @@
+use crate::output;
+use reqwest::Url;
+use std::io::{self, Write};
+
+/// Result of comparing the crawl with a list of known URLs.
+pub struct OrphanReport {
+    /// Known URLs which the crawl never reached.
+    pub orphans: Vec<Url>,
+    /// Crawled URLs absent from the known list.
+    pub missing: Vec<Url>,
+    /// Orphans which answered 200 and were added to the sitemap.
+    pub merged: Vec<Url>,
+    /// Entries of the known list which are not valid URLs of the mapped site.
+    pub ignored: Vec<String>,
+}
+
+impl OrphanReport {
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        output::write_atomic(path, |out| {
+            writeln!(out, "# Orphans: known URLs not reachable by crawl ({})", self.orphans.len())?;
+            for url in self.orphans.iter() {
+                if self.merged.contains(url) {
+                    writeln!(out, "{} (merged into sitemap)", url)?;
+                } else {
+                    writeln!(out, "{}", url)?;
+                }
+            }
+            writeln!(out, "\n# Crawled URLs missing from the known list ({})", self.missing.len())?;
+            for url in self.missing.iter() {
+                writeln!(out, "{}", url)?;
+            }
+            if !self.ignored.is_empty() {
+                writeln!(out, "\n# Ignored entries of the known list ({})", self.ignored.len())?;
+                for entry in self.ignored.iter() {
+                    writeln!(out, "{}", entry)?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn report_sections() {
+        let url = |path: &str| Url::parse("https://foo.bar/").unwrap().join(path).unwrap();
+        let report = OrphanReport {
+            orphans: vec![url("/old"), url("/landing")],
+            missing: vec![url("/new")],
+            merged: vec![url("/landing")],
+            ignored: vec![String::from("https://other.host/")],
+        };
+        let path = env::temp_dir().join(format!("xml_sitemapper_orphans_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        report.write(path).unwrap();
+        let content = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(
+            content,
+            "# Orphans: known URLs not reachable by crawl (2)\n\
+             https://foo.bar/old\n\
+             https://foo.bar/landing (merged into sitemap)\n\
+             \n\
+             # Crawled URLs missing from the known list (1)\n\
+             https://foo.bar/new\n\
+             \n\
+             # Ignored entries of the known list (1)\n\
+             https://other.host/\n"
+        );
+    }
+}
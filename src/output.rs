@@ -0,0 +1,355 @@
+use crate::options::{OutputFormat, Options};
+use crate::xml_file_writer::XmlWriter;
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use reqwest::Url;
+use serde_json::json;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+
+/// One sitemap url with everything the output formats may write about it.
+pub struct OutputEntry {
+    pub url: Url,
+    pub priority: f64,
+    pub lastmod: Option<String>,
+    /// Last-Modified of the page, kept apart from `lastmod` for the feeds.
+    pub modified: Option<String>,
+    pub status: Option<u16>,
+}
+
+/// A file format the crawled urls can be written in. Entries come already sorted.
+pub trait SitemapFormat {
+    /// Appended to the name of the sitemap without its `.xml`, e.g. `.txt`.
+    fn extension(&self) -> &'static str;
+
+    fn write(&self, path: &str, entries: &[OutputEntry]) -> io::Result<()>;
+}
+
+/// `crawl_time` stands in for the update time of a feed without any modified page.
+pub fn format(kind: OutputFormat, options: &Options, site: &Url, crawl_time: DateTime<Utc>) -> Box<dyn SitemapFormat> {
+    let feed = Feed {
+        site: site.clone(),
+        size: options.feed_size,
+        author: options
+            .feed_author
+            .clone()
+            .unwrap_or_else(|| String::from(site.host_str().unwrap_or(site.as_str()))),
+        crawl_time,
+    };
+    match kind {
+        OutputFormat::Xml => Box::new(XmlSitemap),
+        OutputFormat::Text => Box::new(TextList),
+        OutputFormat::Csv => Box::new(CsvList),
+        OutputFormat::Json => Box::new(JsonList),
+        OutputFormat::Rss => Box::new(RssFeed(feed)),
+        OutputFormat::Atom => Box::new(AtomFeed(feed)),
+    }
+}
+
+/// Same guarantee as `XmlWriter`: the file is replaced only once it is completely written.
+pub fn write_atomic<F: FnOnce(&mut BufWriter<File>) -> io::Result<()>>(path: &str, write: F) -> io::Result<()> {
+    let tmp_path = format!("{}.tmp", path);
+    let result = File::create(&tmp_path).and_then(|file| {
+        let mut out = BufWriter::new(file);
+        write(&mut out)?;
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp_path, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+pub struct XmlSitemap;
+
+impl SitemapFormat for XmlSitemap {
+    fn extension(&self) -> &'static str {
+        ".xml"
+    }
+
+    fn write(&self, path: &str, entries: &[OutputEntry]) -> io::Result<()> {
+        let mut writer = XmlWriter::create(path)?;
+        writer.comment(String::from("=== Created with XmlSiteMapper-rs ==="))?;
+        writer.open_element_attr(
+            String::from("urlset"),
+            String::from("xmlns"),
+            String::from("http://www.sitemaps.org/schemas/sitemap/0.9"),
+        )?;
+        for entry in entries {
+            writer.open_element(String::from("url"))?;
+
+            writer.write_element(String::from("loc"), String::from(entry.url.as_str()))?;
+
+            if let Some(lastmod) = &entry.lastmod {
+                writer.write_element(String::from("lastmod"), lastmod.clone())?;
+            }
+
+            writer.write_element(String::from("priority"), format!("{0:.1}", entry.priority))?;
+
+            writer.close_element()?;
+        }
+        writer.close_element()?;
+        writer.finish()
+    }
+}
+
+/// The plain text sitemap format: one url per line.
+pub struct TextList;
+
+impl SitemapFormat for TextList {
+    fn extension(&self) -> &'static str {
+        ".txt"
+    }
+
+    fn write(&self, path: &str, entries: &[OutputEntry]) -> io::Result<()> {
+        write_atomic(path, |out| {
+            for entry in entries {
+                writeln!(out, "{}", entry.url)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        String::from(value)
+    }
+}
+
+pub struct CsvList;
+
+impl SitemapFormat for CsvList {
+    fn extension(&self) -> &'static str {
+        ".csv"
+    }
+
+    fn write(&self, path: &str, entries: &[OutputEntry]) -> io::Result<()> {
+        write_atomic(path, |out| {
+            writeln!(out, "url,priority,lastmod,status")?;
+            for entry in entries {
+                writeln!(
+                    out,
+                    "{},{:.1},{},{}",
+                    csv_field(entry.url.as_str()),
+                    entry.priority,
+                    entry.lastmod.as_deref().unwrap_or(""),
+                    entry.status.map(|s| s.to_string()).unwrap_or_default()
+                )?;
+            }
+            Ok(())
+        })
+    }
+}
+
+pub struct JsonList;
+
+impl SitemapFormat for JsonList {
+    fn extension(&self) -> &'static str {
+        ".json"
+    }
+
+    fn write(&self, path: &str, entries: &[OutputEntry]) -> io::Result<()> {
+        let urls: Vec<serde_json::Value> = entries
+            .iter()
+            .map(|e| {
+                json!({
+                    "url": e.url.as_str(),
+                    "priority": (e.priority * 10.0).round() / 10.0,
+                    "lastmod": e.lastmod,
+                    "status": e.status,
+                })
+            })
+            .collect();
+        write_atomic(path, |out| {
+            serde_json::to_writer_pretty(&mut *out, &json!({ "urls": urls }))?;
+            writeln!(out)
+        })
+    }
+}
+
+pub struct Feed {
+    site: Url,
+    size: usize,
+    author: String,
+    crawl_time: DateTime<Utc>,
+}
+
+impl Feed {
+    /// The `size` most recently modified entries, newest first. Entries without Last-Modified are left out.
+    fn recent<'a>(&self, entries: &'a [OutputEntry]) -> Vec<(&'a OutputEntry, DateTime<FixedOffset>)> {
+        let mut recent: Vec<(&OutputEntry, DateTime<FixedOffset>)> = entries
+            .iter()
+            .filter_map(|e| Some((e, DateTime::parse_from_rfc3339(e.modified.as_deref()?).ok()?)))
+            .collect();
+        recent.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.url.as_str().cmp(b.0.url.as_str())));
+        recent.truncate(self.size);
+        recent
+    }
+}
+
+pub struct RssFeed(Feed);
+
+impl SitemapFormat for RssFeed {
+    fn extension(&self) -> &'static str {
+        ".rss.xml"
+    }
+
+    fn write(&self, path: &str, entries: &[OutputEntry]) -> io::Result<()> {
+        let mut writer = XmlWriter::create(path)?;
+        writer.open_element_attr(String::from("rss"), String::from("version"), String::from("2.0"))?;
+        writer.open_element(String::from("channel"))?;
+        writer.write_element(String::from("title"), format!("Recently modified pages of {}", self.0.site))?;
+        writer.write_element(String::from("link"), String::from(self.0.site.as_str()))?;
+        writer.write_element(String::from("description"), String::from("Created with XmlSiteMapper-rs"))?;
+        for (entry, modified) in self.0.recent(entries) {
+            writer.open_element(String::from("item"))?;
+            writer.write_element(String::from("title"), String::from(entry.url.as_str()))?;
+            writer.write_element(String::from("link"), String::from(entry.url.as_str()))?;
+            writer.write_element(String::from("guid"), String::from(entry.url.as_str()))?;
+            writer.write_element(String::from("pubDate"), modified.to_rfc2822())?;
+            writer.close_element()?;
+        }
+        writer.close_element()?;
+        writer.close_element()?;
+        writer.finish()
+    }
+}
+
+pub struct AtomFeed(Feed);
+
+impl SitemapFormat for AtomFeed {
+    fn extension(&self) -> &'static str {
+        ".atom.xml"
+    }
+
+    fn write(&self, path: &str, entries: &[OutputEntry]) -> io::Result<()> {
+        let recent = self.0.recent(entries);
+        let updated = match recent.first() {
+            Some((_, modified)) => modified.to_rfc3339_opts(SecondsFormat::Secs, true),
+            None => self.0.crawl_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+        };
+        let mut writer = XmlWriter::create(path)?;
+        writer.open_element_attr(String::from("feed"), String::from("xmlns"), String::from("http://www.w3.org/2005/Atom"))?;
+        writer.write_element(String::from("title"), format!("Recently modified pages of {}", self.0.site))?;
+        writer.write_element(String::from("id"), String::from(self.0.site.as_str()))?;
+        writer.write_element(String::from("updated"), updated)?;
+        writer.open_element(String::from("author"))?;
+        writer.write_element(String::from("name"), self.0.author.clone())?;
+        writer.close_element()?;
+        writer.open_element_attr(String::from("link"), String::from("href"), String::from(self.0.site.as_str()))?;
+        writer.close_element()?;
+        for (entry, modified) in recent {
+            writer.open_element(String::from("entry"))?;
+            writer.write_element(String::from("title"), String::from(entry.url.as_str()))?;
+            writer.write_element(String::from("id"), String::from(entry.url.as_str()))?;
+            writer.open_element_attr(String::from("link"), String::from("href"), String::from(entry.url.as_str()))?;
+            writer.close_element()?;
+            writer.write_element(String::from("updated"), modified.to_rfc3339_opts(SecondsFormat::Secs, true))?;
+            writer.close_element()?;
+        }
+        writer.close_element()?;
+        writer.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::Path;
+
+    fn path(name: &str) -> String {
+        let path = env::temp_dir().join(format!("xml_sitemapper_{}_{}", name, std::process::id()));
+        String::from(path.to_str().unwrap())
+    }
+
+    #[test]
+    fn write_atomic_replaces_only_on_success() {
+        let path = path("atomic.txt");
+        fs::write(&path, "old").unwrap();
+        let failed = write_atomic(&path, |out| {
+            writeln!(out, "partial")?;
+            Err(io::Error::other("disk full"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert!(!Path::new(&format!("{}.tmp", path)).exists());
+        write_atomic(&path, |out| writeln!(out, "new")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    fn entries() -> Vec<OutputEntry> {
+        let entry = |url: &str, priority, modified: Option<&str>| OutputEntry {
+            url: Url::parse(url).unwrap(),
+            priority,
+            lastmod: modified.map(|m| String::from(&m[..10])),
+            modified: modified.map(String::from),
+            status: Some(200),
+        };
+        vec![
+            entry("https://foo.bar/", 1.0, Some("2024-03-01T10:00:00+00:00")),
+            entry("https://foo.bar/a,b", 0.84, None),
+            entry("https://foo.bar/new", 0.5, Some("2024-03-05T08:30:00+00:00")),
+        ]
+    }
+
+    fn written(kind: OutputFormat, options: &Options) -> String {
+        let site = Url::parse("https://foo.bar/").unwrap();
+        let crawl_time = DateTime::parse_from_rfc3339("2024-04-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        let format = format(kind, options, &site, crawl_time);
+        let path = path(&format!("sitemap{}", format.extension()));
+        format.write(&path, &entries()).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        content
+    }
+
+    #[test]
+    fn lists() {
+        let options = Options::default();
+        assert_eq!(written(OutputFormat::Text, &options), "https://foo.bar/\nhttps://foo.bar/a,b\nhttps://foo.bar/new\n");
+        assert_eq!(
+            written(OutputFormat::Csv, &options),
+            "url,priority,lastmod,status\nhttps://foo.bar/,1.0,2024-03-01,200\n\"https://foo.bar/a,b\",0.8,,200\nhttps://foo.bar/new,0.5,2024-03-05,200\n"
+        );
+        let json: serde_json::Value = serde_json::from_str(&written(OutputFormat::Json, &options)).unwrap();
+        assert_eq!(json["urls"][1], json!({ "url": "https://foo.bar/a,b", "priority": 0.8, "lastmod": null, "status": 200 }));
+        let xml = written(OutputFormat::Xml, &options);
+        assert!(xml.contains("<loc>https://foo.bar/a,b</loc>"));
+        assert!(xml.contains("<lastmod>2024-03-05</lastmod>"));
+        assert!(xml.contains("<priority>0.8</priority>"));
+    }
+
+    #[test]
+    fn feeds_list_recently_modified_pages() {
+        let mut options = Options::default();
+        options.set("feed_size", "1").unwrap();
+        let rss = written(OutputFormat::Rss, &options);
+        assert!(rss.contains("<guid>https://foo.bar/new</guid>"));
+        assert!(rss.contains("<pubDate>Tue, 5 Mar 2024 08:30:00 +0000</pubDate>"));
+        assert!(!rss.contains("<guid>https://foo.bar/</guid>"));
+        let atom = written(OutputFormat::Atom, &options);
+        assert!(atom.contains("<name>foo.bar</name>"));
+        assert!(atom.contains("<updated>2024-03-05T08:30:00Z</updated>"));
+        assert_eq!(atom.matches("<entry>").count(), 1);
+    }
+
+    #[test]
+    fn empty_feed_uses_the_crawl_time() {
+        let mut options = Options::default();
+        options.set("feed_author", "Jo").unwrap();
+        let site = Url::parse("https://foo.bar/").unwrap();
+        let crawl_time = DateTime::parse_from_rfc3339("2024-04-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        let path = path("empty.atom.xml");
+        format(OutputFormat::Atom, &options, &site, crawl_time).write(&path, &[]).unwrap();
+        let atom = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(atom.contains("<updated>2024-04-01T00:00:00Z</updated>"));
+        assert!(atom.contains("<name>Jo</name>"));
+        assert!(!atom.contains("<entry>"));
+    }
+}
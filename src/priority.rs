@@ -0,0 +1,220 @@
+use crate::link_graph::{self, LinkGraph};
+use regex::Regex;
+use reqwest::{StatusCode, Url};
+use std::collections::{HashMap, HashSet};
+
+/// Everything known about a crawled page at the moment its priority is computed.
+pub struct PageInfo {
+    pub url: Url,
+    pub depth: usize,
+    pub inlinks: usize,
+    pub status: StatusCode,
+    pub content_type: Option<String>,
+    pub content_length: Option<u64>,
+}
+
+pub trait PriorityStrategy {
+    /// Called once after the crawl, before any `priority` call.
+    fn prepare(&mut self, _graph: &LinkGraph, _pages: &HashSet<&Url>) {}
+
+    /// Returns the new priority given the one computed by the previous strategy in a chain
+    /// (1.0 for the first one).
+    fn priority(&self, page: &PageInfo, priority: f64) -> f64;
+}
+
+/// 1.0 minus 0.1 for every path segment and query parameter.
+pub struct SegmentCount;
+
+impl PriorityStrategy for SegmentCount {
+    fn priority(&self, page: &PageInfo, mut priority: f64) -> f64 {
+        let url = &page.url;
+        let seg = url.path_segments();
+        match seg {
+            Some(seg) => {
+                priority -= 0.1 * (seg.count() as f64 - 1.0 + url.query_pairs().count() as f64);
+            }
+            None => {
+                priority -= 0.1 * (url.query_pairs().count() as f64);
+            }
+        }
+        priority
+    }
+}
+
+/// Replaces the priority with the number of inlinks, logarithmically scaled to 0.1..1.0.
+#[derive(Default)]
+pub struct Inlinks {
+    scores: HashMap<Url, f64>,
+}
+
+impl Inlinks {
+    pub fn new() -> Inlinks {
+        Inlinks {
+            scores: HashMap::new(),
+        }
+    }
+}
+
+impl PriorityStrategy for Inlinks {
+    fn prepare(&mut self, graph: &LinkGraph, pages: &HashSet<&Url>) {
+        let scores = graph
+            .inlink_counts(pages)
+            .into_iter()
+            .map(|(url, count)| (url, (1.0 + count as f64).ln()))
+            .collect();
+        self.scores = link_graph::scale_to_priority(scores);
+    }
+
+    fn priority(&self, page: &PageInfo, priority: f64) -> f64 {
+        *self.scores.get(&page.url).unwrap_or(&priority)
+    }
+}
+
+/// Replaces the priority with the PageRank score, linearly scaled to 0.1..1.0.
+#[derive(Default)]
+pub struct PageRank {
+    scores: HashMap<Url, f64>,
+}
+
+impl PageRank {
+    pub fn new() -> PageRank {
+        PageRank {
+            scores: HashMap::new(),
+        }
+    }
+}
+
+impl PriorityStrategy for PageRank {
+    fn prepare(&mut self, graph: &LinkGraph, pages: &HashSet<&Url>) {
+        self.scores = link_graph::scale_to_priority(graph.page_rank(pages));
+    }
+
+    fn priority(&self, page: &PageInfo, priority: f64) -> f64 {
+        *self.scores.get(&page.url).unwrap_or(&priority)
+    }
+}
+
+/// Adds the change for every regex from change_prio.cfg matching the URL.
+pub struct RegexAdjust {
+    rules: Vec<(Regex, f64)>,
+}
+
+impl RegexAdjust {
+    pub fn new(rules: Vec<(Regex, f64)>) -> RegexAdjust {
+        RegexAdjust { rules }
+    }
+}
+
+impl PriorityStrategy for RegexAdjust {
+    fn priority(&self, page: &PageInfo, mut priority: f64) -> f64 {
+        for (re, change) in self.rules.iter() {
+            if re.is_match(page.url.as_str()) {
+                priority += change;
+            }
+        }
+        priority
+    }
+}
+
+/// Applies strategies one after another, each receiving the result of the previous one.
+#[derive(Default)]
+pub struct PriorityChain {
+    strategies: Vec<Box<dyn PriorityStrategy>>,
+}
+
+impl PriorityChain {
+    pub fn new() -> PriorityChain {
+        PriorityChain {
+            strategies: Vec::new(),
+        }
+    }
+
+    pub fn then(mut self, strategy: Box<dyn PriorityStrategy>) -> PriorityChain {
+        self.strategies.push(strategy);
+        self
+    }
+}
+
+impl PriorityStrategy for PriorityChain {
+    fn prepare(&mut self, graph: &LinkGraph, pages: &HashSet<&Url>) {
+        for strategy in self.strategies.iter_mut() {
+            strategy.prepare(graph, pages);
+        }
+    }
+
+    fn priority(&self, page: &PageInfo, priority: f64) -> f64 {
+        self.strategies
+            .iter()
+            .fold(priority, |priority, strategy| strategy.priority(page, priority))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(url: &str) -> PageInfo {
+        PageInfo {
+            url: Url::parse(url).unwrap(),
+            depth: 0,
+            inlinks: 0,
+            status: StatusCode::OK,
+            content_type: None,
+            content_length: None,
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn segment_count() {
+        assert!(close(SegmentCount.priority(&page("https://foo.bar/"), 1.0), 1.0));
+        assert!(close(SegmentCount.priority(&page("https://foo.bar/a/b"), 1.0), 0.9));
+        assert!(close(SegmentCount.priority(&page("https://foo.bar/a?x=1&y=2"), 1.0), 0.8));
+    }
+
+    #[test]
+    fn regex_adjust_adds_every_matching_rule() {
+        let strategy = RegexAdjust::new(vec![
+            (Regex::new("/blog/").unwrap(), -0.2),
+            (Regex::new(r"\.html$").unwrap(), 0.1),
+        ]);
+        assert!(close(strategy.priority(&page("https://foo.bar/blog/a.html"), 0.5), 0.4));
+        assert!(close(strategy.priority(&page("https://foo.bar/a.html"), 0.5), 0.6));
+        assert!(close(strategy.priority(&page("https://foo.bar/"), 0.5), 0.5));
+    }
+
+    #[test]
+    fn chain_feeds_each_result_into_the_next() {
+        let chain = PriorityChain::new()
+            .then(Box::new(SegmentCount))
+            .then(Box::new(RegexAdjust::new(vec![(Regex::new("/docs").unwrap(), 0.3)])));
+        assert!(close(chain.priority(&page("https://foo.bar/docs/a/b"), 1.0), 1.1));
+        assert!(close(PriorityChain::new().priority(&page("https://foo.bar/"), 0.4), 0.4));
+    }
+
+    #[test]
+    fn inlinks_scale_and_fall_back() {
+        let urls: Vec<Url> = ["https://foo.bar/", "https://foo.bar/a", "https://foo.bar/b"]
+            .iter()
+            .map(|u| Url::parse(u).unwrap())
+            .collect();
+        let mut graph = LinkGraph::new();
+        for url in urls.iter() {
+            graph.add_node(url);
+        }
+        graph.add_edge(&urls[0], &urls[1]);
+        graph.add_edge(&urls[2], &urls[1]);
+        graph.add_edge(&urls[1], &urls[0]);
+        let pages: HashSet<&Url> = urls.iter().collect();
+        let mut strategy = Inlinks::new();
+        strategy.prepare(&graph, &pages);
+        assert!(close(strategy.priority(&page("https://foo.bar/a"), 0.5), 1.0));
+        assert!(close(strategy.priority(&page("https://foo.bar/b"), 0.5), 0.1));
+        let root = strategy.priority(&page("https://foo.bar/"), 0.5);
+        assert!(root > 0.1 && root < 1.0);
+        assert!(close(strategy.priority(&page("https://foo.bar/unknown"), 0.5), 0.5));
+    }
+}
@@ -0,0 +1,63 @@
+use chrono::{DateTime, Utc};
+use reqwest::blocking::Response;
+use reqwest::StatusCode;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 429 and 5xx responses are worth another try, everything else is final.
+pub fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// The server asks to slow down as a whole, not just for this URL.
+pub fn is_overload(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE
+}
+
+/// `base * 2^attempt` capped at `max`, with up to 50% random jitter subtracted so that
+/// retries of several URLs do not line up.
+pub fn backoff(attempt: u32, base: u64, max: u64) -> Duration {
+    let delay = base.saturating_mul(1u64 << attempt.min(20)).min(max);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or(0);
+    let jitter = if delay > 1 { nanos % (delay / 2 + 1) } else { 0 };
+    Duration::from_millis(delay - jitter)
+}
+
+/// Value of the Retry-After header, given either in seconds or as an HTTP date.
+pub fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get("Retry-After")?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retryable_statuses() {
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable(StatusCode::BAD_GATEWAY));
+        assert!(!is_retryable(StatusCode::NOT_FOUND));
+        assert!(!is_retryable(StatusCode::OK));
+        assert!(is_overload(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_overload(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max_with_jitter() {
+        for attempt in 0..4 {
+            let full = 100 << attempt;
+            let delay = backoff(attempt, 100, 10_000).as_millis() as u64;
+            assert!(delay <= full && delay >= full / 2, "attempt {}: {}", attempt, delay);
+        }
+        assert!(backoff(40, 100, 5_000) <= Duration::from_millis(5_000));
+        assert!(backoff(40, 100, 5_000) >= Duration::from_millis(2_500));
+        assert_eq!(backoff(3, 0, 5_000), Duration::from_millis(0));
+    }
+}
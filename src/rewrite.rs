@@ -0,0 +1,122 @@
+use crate::options::Options;
+use regex::Regex;
+use reqwest::Url;
+use std::collections::HashMap;
+
+struct HostRule {
+    host: String,
+    port: Option<u16>,
+    target: Url,
+}
+
+/// Rewrites crawled URLs into the ones published in the sitemap, e.g. staging to production.
+pub struct Rewriter {
+    hosts: Vec<HostRule>,
+    paths: Vec<(Regex, String)>,
+}
+
+impl Rewriter {
+    pub fn new(options: &Options) -> Rewriter {
+        let hosts = options
+            .rewrite_hosts
+            .iter()
+            .filter_map(|(from, to)| {
+                let from = Url::parse(&format!("http://{}/", from)).ok()?;
+                Some(HostRule {
+                    host: String::from(from.host_str()?),
+                    port: from.port(),
+                    target: Url::parse(to).ok()?,
+                })
+            })
+            .collect();
+        let paths = options
+            .rewrite_paths
+            .iter()
+            .filter_map(|(re, replacement)| Some((Regex::new(re).ok()?, replacement.clone())))
+            .collect();
+        Rewriter { hosts, paths }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty() && self.paths.is_empty()
+    }
+
+    pub fn rewrite(&self, url: &Url) -> Url {
+        let mut result = url.clone();
+        let host = url.host_str().unwrap_or("");
+        if let Some(rule) = self
+            .hosts
+            .iter()
+            .find(|rule| rule.host.eq_ignore_ascii_case(host) && rule.port == url.port())
+        {
+            let mut target = rule.target.clone();
+            target.set_path(url.path());
+            target.set_query(url.query());
+            result = target;
+        }
+        for (re, replacement) in self.paths.iter() {
+            let path = re.replace(result.path(), replacement.as_str()).into_owned();
+            result.set_path(&path);
+        }
+        result
+    }
+
+    /// Rewrites every URL of the map. If several URLs end up the same, the highest priority wins.
+    pub fn rewrite_map(&self, map: HashMap<Url, f64>) -> HashMap<Url, f64> {
+        let mut result: HashMap<Url, f64> = HashMap::new();
+        for (url, priority) in map {
+            let entry = result.entry(self.rewrite(&url)).or_insert(priority);
+            if *entry < priority {
+                *entry = priority;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewriter(rules: &[(&str, &str)]) -> Rewriter {
+        let mut options = Options::default();
+        for (key, value) in rules {
+            options.set(key, value).unwrap();
+        }
+        Rewriter::new(&options)
+    }
+
+    fn rewrite(rewriter: &Rewriter, url: &str) -> String {
+        String::from(rewriter.rewrite(&Url::parse(url).unwrap()).as_str())
+    }
+
+    #[test]
+    fn hosts_are_matched_with_their_port() {
+        let rewriter = rewriter(&[("rewrite_host", "staging.foo.internal:8080 -> https://www.foo.bar")]);
+        assert_eq!(rewrite(&rewriter, "http://staging.foo.internal:8080/a/b?x=1"), "https://www.foo.bar/a/b?x=1");
+        assert_eq!(rewrite(&rewriter, "http://STAGING.foo.internal:8080/"), "https://www.foo.bar/");
+        assert_eq!(rewrite(&rewriter, "http://staging.foo.internal/a"), "http://staging.foo.internal/a");
+    }
+
+    #[test]
+    fn paths_after_hosts() {
+        let rewriter = rewriter(&[
+            ("rewrite_host", "staging.foo.internal -> https://www.foo.bar"),
+            ("rewrite_path", "^/staging/(.*) -> /$1"),
+        ]);
+        assert_eq!(rewrite(&rewriter, "http://staging.foo.internal/staging/docs/"), "https://www.foo.bar/docs/");
+        assert!(!rewriter.is_empty());
+        assert!(Rewriter::new(&Options::default()).is_empty());
+    }
+
+    #[test]
+    fn merged_urls_keep_the_highest_priority() {
+        let rewriter = rewriter(&[("rewrite_path", "^/v[12]/ -> /")]);
+        let mut map = HashMap::new();
+        map.insert(Url::parse("https://foo.bar/v1/a").unwrap(), 0.4);
+        map.insert(Url::parse("https://foo.bar/v2/a").unwrap(), 0.7);
+        let map = rewriter.rewrite_map(map);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map[&Url::parse("https://foo.bar/a").unwrap()], 0.7);
+    }
+}
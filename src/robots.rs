@@ -0,0 +1,196 @@
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use reqwest::Url;
+use select::document::Document;
+use select::predicate::Name;
+
+struct Rule {
+    allow: bool,
+    length: usize,
+    pattern: Regex,
+}
+
+/// Allow and Disallow rules of a robots.txt file which apply to the mapper.
+#[derive(Default)]
+pub struct Robots {
+    rules: Vec<Rule>,
+}
+
+/// Turns a robots.txt path pattern with `*` and a trailing `$` into an anchored regex.
+fn pattern_regex(pattern: &str) -> Option<Regex> {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let parts: Vec<String> = pattern.split('*').map(regex::escape).collect();
+    let end = if anchored { "$" } else { "" };
+    Regex::new(&format!("^{}{}", parts.join(".*"), end)).ok()
+}
+
+impl Robots {
+    /// Reads the groups for the product token of `user_agent` (`XmlSiteMapper-rs` in
+    /// `XmlSiteMapper-rs/0.1.0`), or the `*` groups when none names it.
+    pub fn parse(content: &str, user_agent: &str) -> Robots {
+        let token = user_agent
+            .split(|c: char| c == '/' || c.is_whitespace())
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+        let mut specific = Vec::new();
+        let mut wildcard = Vec::new();
+        let mut agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+        let mut named = false;
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim().to_lowercase(), value.trim()),
+                None => {
+                    continue;
+                }
+            };
+            match key.as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    let agent = value.to_lowercase();
+                    named |= agent == token;
+                    agents.push(agent);
+                }
+                "allow" | "disallow" => {
+                    in_rules = true;
+                    if value.is_empty() {
+                        continue;
+                    }
+                    let pattern = match pattern_regex(value) {
+                        Some(pattern) => pattern,
+                        None => {
+                            continue;
+                        }
+                    };
+                    let rule = || Rule {
+                        allow: key == "allow",
+                        length: value.len(),
+                        pattern: pattern.clone(),
+                    };
+                    if agents.contains(&token) {
+                        specific.push(rule());
+                    }
+                    if agents.iter().any(|a| a == "*") {
+                        wildcard.push(rule());
+                    }
+                }
+                _ => {}
+            }
+        }
+        Robots {
+            rules: if named { specific } else { wildcard },
+        }
+    }
+
+    /// The longest matching rule decides, Allow wins a tie.
+    pub fn allows(&self, url: &Url) -> bool {
+        let mut target = String::from(url.path());
+        if let Some(query) = url.query() {
+            target = format!("{}?{}", target, query);
+        }
+        if target == "/robots.txt" {
+            return true;
+        }
+        self.rules
+            .iter()
+            .filter(|r| r.pattern.is_match(&target))
+            .max_by_key(|r| (r.length, r.allow))
+            .is_none_or(|r| r.allow)
+    }
+}
+
+fn has_noindex(directives: &str) -> bool {
+    directives
+        .to_lowercase()
+        .split(|c: char| c == ',' || c == ':' || c.is_whitespace())
+        .any(|d| d == "noindex" || d == "none")
+}
+
+/// `X-Robots-Tag: noindex` (or `none`), for any crawler.
+pub fn header_noindex(headers: &HeaderMap) -> bool {
+    headers
+        .get_all("X-Robots-Tag")
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .any(has_noindex)
+}
+
+/// `<meta name="robots" content="noindex">` (or `none`).
+pub fn meta_noindex(html: &Document) -> bool {
+    html.find(Name("meta")).any(|m| {
+        m.attr("name").is_some_and(|n| n.eq_ignore_ascii_case("robots")) && m.attr("content").is_some_and(has_noindex)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allows(robots: &Robots, path: &str) -> bool {
+        robots.allows(&Url::parse(&format!("https://foo.bar{}", path)).unwrap())
+    }
+
+    #[test]
+    fn named_group_wins_over_wildcard() {
+        let content = "User-agent: *\nDisallow: /\n\nUser-agent: OtherBot\nUser-agent: XmlSiteMapper-rs\nDisallow: /private/\n";
+        let robots = Robots::parse(content, "XmlSiteMapper-rs/0.1.0");
+        assert!(allows(&robots, "/public/"));
+        assert!(!allows(&robots, "/private/a.html"));
+        let other = Robots::parse(content, "SomeoneElse/1.0");
+        assert!(!allows(&other, "/public/"));
+    }
+
+    #[test]
+    fn groups_end_at_the_next_user_agent() {
+        let content = "User-agent: xmlsitemapper-rs\nDisallow: /a/\nUser-agent: *\nDisallow: /b/\n";
+        let robots = Robots::parse(content, "XmlSiteMapper-rs/0.1.0");
+        assert!(!allows(&robots, "/a/"));
+        assert!(allows(&robots, "/b/"));
+    }
+
+    #[test]
+    fn longest_match_wins_and_allow_wins_ties() {
+        let content = "User-agent: *\nDisallow: /shop/\nAllow: /shop/public/\nDisallow: /x\nAllow: /x\n";
+        let robots = Robots::parse(content, "XmlSiteMapper-rs");
+        assert!(!allows(&robots, "/shop/cart"));
+        assert!(allows(&robots, "/shop/public/item"));
+        assert!(allows(&robots, "/x"));
+        assert!(allows(&robots, "/other"));
+    }
+
+    #[test]
+    fn wildcards_and_anchors() {
+        let content = "User-agent: *\nDisallow: /*.pdf$\nDisallow: /*?session=\nDisallow:\n";
+        let robots = Robots::parse(content, "XmlSiteMapper-rs");
+        assert!(!allows(&robots, "/docs/a.pdf"));
+        assert!(allows(&robots, "/docs/a.pdf.html"));
+        assert!(!allows(&robots, "/page?session=1"));
+        assert!(allows(&robots, "/page?id=1"));
+    }
+
+    #[test]
+    fn robots_txt_itself_is_always_allowed() {
+        let robots = Robots::parse("User-agent: *\nDisallow: /\n", "XmlSiteMapper-rs");
+        assert!(!allows(&robots, "/"));
+        assert!(allows(&robots, "/robots.txt"));
+    }
+
+    #[test]
+    fn noindex_directives() {
+        assert!(has_noindex("noindex, nofollow"));
+        assert!(has_noindex("googlebot: NOINDEX"));
+        assert!(has_noindex("none"));
+        assert!(!has_noindex("nofollow, noarchive"));
+        let html = Document::from("<html><head><meta name=\"ROBOTS\" content=\"noindex\"></head></html>");
+        assert!(meta_noindex(&html));
+        assert!(!meta_noindex(&Document::from("<meta name=\"description\" content=\"noindex\">")));
+    }
+}
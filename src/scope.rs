@@ -0,0 +1,198 @@
+use crate::options::{Options, ScopeKind};
+use reqwest::Url;
+use url::Host;
+
+/// Scheme, host and explicit port of a URL.
+#[derive(PartialEq)]
+struct Origin {
+    scheme: String,
+    host: Host<String>,
+    port: Option<u16>,
+}
+
+impl Origin {
+    fn of(url: &Url) -> Option<Origin> {
+        let host = match url.host()? {
+            Host::Domain(domain) => Host::Domain(domain.to_lowercase()),
+            Host::Ipv4(ip) => Host::Ipv4(ip),
+            Host::Ipv6(ip) => Host::Ipv6(ip),
+        };
+        Some(Origin {
+            scheme: url.scheme().to_lowercase(),
+            host,
+            port: url.port(),
+        })
+    }
+
+    /// Parses scope_hosts entries like `foo.bar`, `https://foo.bar:8080`, `127.0.0.1:8080` or `[::1]:3000`.
+    /// Entries without a scheme get the one of the site root.
+    fn parse(entry: &str, scheme: &str) -> Option<Origin> {
+        if entry.contains("://") {
+            Origin::of(&Url::parse(entry).ok()?)
+        } else {
+            Origin::of(&Url::parse(&format!("{}://{}/", scheme, entry)).ok()?)
+        }
+    }
+
+    /// Domain the subdomains of this origin belong to. IP literals and single-label hosts
+    /// such as `localhost` have no subdomains besides their own `*.localhost` names.
+    fn subdomain_root(&self) -> Option<&str> {
+        match &self.host {
+            Host::Domain(domain) => Some(domain.strip_prefix("www.").unwrap_or(domain)),
+            _ => None,
+        }
+    }
+}
+
+/// Decides which URLs belong to the mapped site.
+pub struct Scope {
+    kind: ScopeKind,
+    origin: Option<Origin>,
+    origins: Vec<Origin>,
+    path_prefix: Option<String>,
+}
+
+impl Scope {
+    pub fn new(main_url: &Url, options: &Options) -> Scope {
+        let mut origins: Vec<Origin> = options
+            .scope_hosts
+            .iter()
+            .filter_map(|entry| Origin::parse(entry, main_url.scheme()))
+            .collect();
+        if let Some(origin) = Origin::of(main_url) {
+            origins.push(origin);
+        }
+        Scope {
+            kind: options.scope,
+            origin: Origin::of(main_url),
+            origins,
+            path_prefix: options.scope_path_prefix.clone(),
+        }
+    }
+
+    fn origin_matches(&self, origin: &Origin) -> bool {
+        let main = match &self.origin {
+            Some(main) => main,
+            None => {
+                return false;
+            }
+        };
+        match self.kind {
+            ScopeKind::Host => origin == main,
+            ScopeKind::Subdomains => {
+                if origin.scheme != main.scheme || origin.port != main.port {
+                    return false;
+                }
+                match (main.subdomain_root(), &origin.host) {
+                    (Some(root), Host::Domain(host)) => {
+                        host == root || host.ends_with(&format!(".{}", root))
+                    }
+                    _ => origin == main,
+                }
+            }
+            ScopeKind::Hosts => self.origins.iter().any(|o| o == origin),
+        }
+    }
+
+    pub fn contains(&self, url: &Url) -> bool {
+        match Origin::of(url) {
+            Some(origin) => {
+                if !self.origin_matches(&origin) {
+                    return false;
+                }
+            }
+            None => {
+                return false;
+            }
+        }
+        match &self.path_prefix {
+            Some(prefix) => url.path().starts_with(prefix.as_str()),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(root: &str, kind: ScopeKind, hosts: &[&str]) -> Scope {
+        let options = Options {
+            scope: kind,
+            scope_hosts: hosts.iter().map(|h| String::from(*h)).collect(),
+            ..Options::default()
+        };
+        Scope::new(&Url::parse(root).unwrap(), &options)
+    }
+
+    fn contains(scope: &Scope, url: &str) -> bool {
+        scope.contains(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn host_compares_scheme_and_port() {
+        let scope = scope("https://Foo.bar/", ScopeKind::Host, &[]);
+        assert!(contains(&scope, "https://foo.bar/a"));
+        assert!(contains(&scope, "https://FOO.BAR:443/a"));
+        assert!(!contains(&scope, "http://foo.bar/a"));
+        assert!(!contains(&scope, "https://foo.bar:8443/a"));
+        assert!(!contains(&scope, "https://www.foo.bar/a"));
+    }
+
+    #[test]
+    fn ip_literals() {
+        let v4 = scope("http://127.0.0.1:8080/", ScopeKind::Subdomains, &[]);
+        assert!(contains(&v4, "http://127.0.0.1:8080/a"));
+        assert!(!contains(&v4, "http://127.0.0.1/a"));
+        assert!(!contains(&v4, "http://127.0.0.2:8080/a"));
+        let v6 = scope("http://[::1]:3000/", ScopeKind::Host, &[]);
+        assert!(contains(&v6, "http://[0:0:0:0:0:0:0:1]:3000/a"));
+        assert!(!contains(&v6, "http://[::1]/a"));
+    }
+
+    #[test]
+    fn localhost_has_only_its_own_subdomains() {
+        let scope = scope("http://localhost:3000/", ScopeKind::Subdomains, &[]);
+        assert!(contains(&scope, "http://localhost:3000/a"));
+        assert!(contains(&scope, "http://api.localhost:3000/a"));
+        assert!(!contains(&scope, "http://localhost:4000/a"));
+        assert!(!contains(&scope, "http://notlocalhost:3000/a"));
+    }
+
+    #[test]
+    fn subdomains_of_the_apex() {
+        let scope = scope("https://www.foo.bar/", ScopeKind::Subdomains, &[]);
+        assert!(contains(&scope, "https://foo.bar/"));
+        assert!(contains(&scope, "https://blog.foo.bar/"));
+        assert!(contains(&scope, "https://a.b.foo.bar/"));
+        assert!(!contains(&scope, "https://evilfoo.bar/"));
+        assert!(!contains(&scope, "http://blog.foo.bar/"));
+        assert!(!contains(&scope, "https://blog.foo.bar:8443/"));
+    }
+
+    #[test]
+    fn hosts_with_mixed_schemes() {
+        let scope = scope("https://foo.bar/", ScopeKind::Hosts, &["blog.foo.bar", "http://shop.foo.bar", "10.0.0.5:8443", "[::1]:3000"]);
+        assert!(contains(&scope, "https://foo.bar/"));
+        assert!(contains(&scope, "https://blog.foo.bar/"));
+        assert!(!contains(&scope, "http://blog.foo.bar/"));
+        assert!(contains(&scope, "http://shop.foo.bar/"));
+        assert!(!contains(&scope, "https://shop.foo.bar/"));
+        assert!(contains(&scope, "https://10.0.0.5:8443/"));
+        assert!(!contains(&scope, "https://10.0.0.5/"));
+        assert!(contains(&scope, "https://[::1]:3000/"));
+        assert!(!contains(&scope, "https://other.bar/"));
+    }
+
+    #[test]
+    fn path_prefix() {
+        let options = Options {
+            scope_path_prefix: Some(String::from("/docs/")),
+            ..Options::default()
+        };
+        let scope = Scope::new(&Url::parse("https://foo.bar/docs/").unwrap(), &options);
+        assert!(contains(&scope, "https://foo.bar/docs/a"));
+        assert!(!contains(&scope, "https://foo.bar/blog/"));
+        assert!(!contains(&scope, "https://foo.bar/"));
+    }
+}
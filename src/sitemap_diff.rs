@@ -0,0 +1,197 @@
+use crate::options::Options;
+use crate::output;
+use crate::url_list::{self, SitemapEntry};
+use reqwest::blocking::Client;
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::io::{self, Write};
+use std::path::Path;
+
+pub struct Change {
+    pub url: String,
+    pub old: SitemapEntry,
+    pub new: SitemapEntry,
+}
+
+/// Differences between two sitemaps, sorted by URL.
+#[derive(Default)]
+pub struct SitemapDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub priority_changed: Vec<Change>,
+    pub lastmod_changed: Vec<Change>,
+}
+
+/// Reads a sitemap or a sitemap index with all sitemaps it points to, gzipped or not.
+/// With `prefer_local` the sitemaps of an index are read from files next to it when they exist.
+pub fn load(location: &str, client: &Client, options: &Options, prefer_local: bool) -> Result<BTreeMap<String, SitemapEntry>, String> {
+    let local_dir = Path::new(location).parent().filter(|_| prefer_local);
+    let root = location;
+    let mut entries = BTreeMap::new();
+    let mut queue = VecDeque::new();
+    let mut seen = HashSet::new();
+    queue.push_back(String::from(location));
+    while let Some(location) = queue.pop_front() {
+        if !seen.insert(location.clone()) {
+            continue;
+        }
+        let bytes = url_list::read_location(&location, root, local_dir, client, options)?;
+        let text = url_list::decode(&bytes).map_err(|e| format!("Unable to decode '{}': {}", location, e))?;
+        let sitemap = url_list::parse_sitemap(&text).map_err(|e| format!("Unable to parse '{}': {}", location, e))?;
+        if sitemap.is_index {
+            queue.extend(sitemap.locs);
+        } else {
+            for entry in sitemap.entries {
+                entries.insert(entry.loc.clone(), entry);
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn same_priority(old: &Option<String>, new: &Option<String>) -> bool {
+    match (old, new) {
+        (Some(old), Some(new)) => match (old.parse::<f64>(), new.parse::<f64>()) {
+            (Ok(old), Ok(new)) => (old - new).abs() < 1e-9,
+            _ => old == new,
+        },
+        _ => old == new,
+    }
+}
+
+pub fn diff(old: &BTreeMap<String, SitemapEntry>, new: &BTreeMap<String, SitemapEntry>) -> SitemapDiff {
+    let mut result = SitemapDiff::default();
+    for (url, entry) in new.iter() {
+        let prev = match old.get(url) {
+            Some(prev) => prev,
+            None => {
+                result.added.push(url.clone());
+                continue;
+            }
+        };
+        let change = || Change { url: url.clone(), old: prev.clone(), new: entry.clone() };
+        if !same_priority(&prev.priority, &entry.priority) {
+            result.priority_changed.push(change());
+        }
+        if prev.lastmod != entry.lastmod {
+            result.lastmod_changed.push(change());
+        }
+    }
+    result.removed = old.keys().filter(|url| !new.contains_key(*url)).cloned().collect();
+    result
+}
+
+fn show(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("-")
+}
+
+impl SitemapDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.priority_changed.is_empty() && self.lastmod_changed.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "Sitemap changes: {} added, {} removed, {} priority changes, {} lastmod changes.",
+            self.added.len(),
+            self.removed.len(),
+            self.priority_changed.len(),
+            self.lastmod_changed.len()
+        )
+    }
+
+    /// Human readable change list, one line per change.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![self.summary()];
+        lines.extend(self.added.iter().map(|url| format!("+ {}", url)));
+        lines.extend(self.removed.iter().map(|url| format!("- {}", url)));
+        lines.extend(self.priority_changed.iter().map(|c| {
+            format!("~ {} priority {} -> {}", c.url, show(&c.old.priority), show(&c.new.priority))
+        }));
+        lines.extend(self.lastmod_changed.iter().map(|c| {
+            format!("~ {} lastmod {} -> {}", c.url, show(&c.old.lastmod), show(&c.new.lastmod))
+        }));
+        lines
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let changes = |changes: &[Change], field: fn(&SitemapEntry) -> &Option<String>| {
+            changes
+                .iter()
+                .map(|c| json!({ "url": c.url, "old": field(&c.old), "new": field(&c.new) }))
+                .collect::<Vec<_>>()
+        };
+        json!({
+            "added": self.added,
+            "removed": self.removed,
+            "priority_changed": changes(&self.priority_changed, |e| &e.priority),
+            "lastmod_changed": changes(&self.lastmod_changed, |e| &e.lastmod),
+        })
+    }
+
+    pub fn write_json(&self, path: &str) -> io::Result<()> {
+        output::write_atomic(path, |out| {
+            serde_json::to_writer_pretty(&mut *out, &self.to_json())?;
+            writeln!(out)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sitemap(entries: &[(&str, Option<&str>, Option<&str>)]) -> BTreeMap<String, SitemapEntry> {
+        entries
+            .iter()
+            .map(|(loc, lastmod, priority)| {
+                let entry = SitemapEntry {
+                    loc: String::from(*loc),
+                    lastmod: lastmod.map(String::from),
+                    priority: priority.map(String::from),
+                };
+                (String::from(*loc), entry)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn added_removed_and_changed() {
+        let old = sitemap(&[
+            ("https://foo.bar/", Some("2024-01-01"), Some("1.0")),
+            ("https://foo.bar/a", None, Some("0.5")),
+            ("https://foo.bar/gone", None, None),
+        ]);
+        let new = sitemap(&[
+            ("https://foo.bar/", Some("2024-02-01"), Some("1")),
+            ("https://foo.bar/a", None, Some("0.80")),
+            ("https://foo.bar/new", None, None),
+        ]);
+        let diff = diff(&old, &new);
+        assert_eq!(diff.added, vec!["https://foo.bar/new"]);
+        assert_eq!(diff.removed, vec!["https://foo.bar/gone"]);
+        assert_eq!(diff.priority_changed.len(), 1);
+        assert_eq!(diff.priority_changed[0].url, "https://foo.bar/a");
+        assert_eq!(diff.lastmod_changed.len(), 1);
+        assert_eq!(diff.lastmod_changed[0].url, "https://foo.bar/");
+        assert_eq!(
+            diff.lines(),
+            vec![
+                "Sitemap changes: 1 added, 1 removed, 1 priority changes, 1 lastmod changes.",
+                "+ https://foo.bar/new",
+                "- https://foo.bar/gone",
+                "~ https://foo.bar/a priority 0.5 -> 0.80",
+                "~ https://foo.bar/ lastmod 2024-01-01 -> 2024-02-01",
+            ]
+        );
+        assert_eq!(diff.to_json()["priority_changed"][0]["new"], "0.80");
+    }
+
+    #[test]
+    fn same_sitemap_is_empty() {
+        let map = sitemap(&[("https://foo.bar/", None, Some("0.5"))]);
+        assert!(diff(&map, &map).is_empty());
+        assert!(!same_priority(&Some(String::from("0.5")), &None));
+        assert!(same_priority(&Some(String::from("high")), &Some(String::from("high"))));
+    }
+}
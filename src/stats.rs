@@ -0,0 +1,216 @@
+use crate::logger::FetchEvent;
+use crate::output;
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::Url;
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Counters collected during the crawl for the summary at the end of a run.
+pub struct CrawlStats {
+    pub started: DateTime<Utc>,
+    pub duration: Duration,
+    pub fetched: usize,
+    pub failed: usize,
+    pub statuses: BTreeMap<u16, usize>,
+    pub content_types: BTreeMap<String, usize>,
+    pub exclusions: BTreeMap<&'static str, usize>,
+    pub depths: BTreeMap<usize, usize>,
+    pub total_bytes: u64,
+    times: Vec<(Url, Duration)>,
+    excluded: HashSet<String>,
+}
+
+impl Default for CrawlStats {
+    fn default() -> Self {
+        CrawlStats {
+            started: Utc::now(),
+            duration: Duration::from_millis(0),
+            fetched: 0,
+            failed: 0,
+            statuses: BTreeMap::new(),
+            content_types: BTreeMap::new(),
+            exclusions: BTreeMap::new(),
+            depths: BTreeMap::new(),
+            total_bytes: 0,
+            times: Vec::new(),
+            excluded: HashSet::new(),
+        }
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+impl CrawlStats {
+    pub fn new() -> CrawlStats {
+        CrawlStats::default()
+    }
+
+    pub fn record(&mut self, event: &FetchEvent) {
+        match event.decision {
+            "skipped" => {
+                self.exclude("content type", event.url.as_str());
+                return;
+            }
+            "excluded" => self.exclude("content type", event.url.as_str()),
+            "noindex" => self.exclude("noindex", event.url.as_str()),
+            "error" => {
+                self.failed += 1;
+                self.exclude("error", event.url.as_str());
+            }
+            _ => {}
+        }
+        self.fetched += 1;
+        if let Some(status) = event.status {
+            *self.statuses.entry(status).or_default() += 1;
+        }
+        if let Some(content_type) = event.content_type {
+            let mime = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+            *self.content_types.entry(mime).or_default() += 1;
+        }
+        *self.depths.entry(event.depth).or_default() += 1;
+        self.total_bytes += event.bytes.unwrap_or(0);
+        self.times.push((event.url.clone(), event.duration));
+    }
+
+    /// Counts a URL left out of the sitemap, once per URL and reason.
+    pub fn exclude(&mut self, reason: &'static str, url: &str) {
+        if self.excluded.insert(format!("{} {}", reason, url)) {
+            *self.exclusions.entry(reason).or_default() += 1;
+        }
+    }
+
+    pub fn average_time(&self) -> Duration {
+        if self.times.is_empty() {
+            return Duration::from_millis(0);
+        }
+        self.times.iter().map(|(_, d)| *d).sum::<Duration>() / self.times.len() as u32
+    }
+
+    pub fn percentile_time(&self, percentile: f64) -> Duration {
+        let mut times: Vec<Duration> = self.times.iter().map(|(_, d)| *d).collect();
+        if times.is_empty() {
+            return Duration::from_millis(0);
+        }
+        times.sort();
+        let rank = (percentile / 100.0 * times.len() as f64).ceil() as usize;
+        times[rank.clamp(1, times.len()) - 1]
+    }
+
+    pub fn slowest(&self, count: usize) -> Vec<(&Url, Duration)> {
+        let mut times: Vec<(&Url, Duration)> = self.times.iter().map(|(u, d)| (u, *d)).collect();
+        times.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        times.truncate(count);
+        times
+    }
+
+    pub fn summary(&self, urls_added: usize) -> Vec<String> {
+        let join = |counts: Vec<String>| if counts.is_empty() { String::from("-") } else { counts.join(", ") };
+        let mut lines = vec![
+            format!("Crawl duration: {:.1} s", self.duration.as_secs_f64()),
+            format!("Pages fetched: {} ({} failed), urls added: {}", self.fetched, self.failed, urls_added),
+            format!("Downloaded: {} bytes", self.total_bytes),
+            format!(
+                "Response time: average {} ms, p95 {} ms",
+                millis(self.average_time()),
+                millis(self.percentile_time(95.0))
+            ),
+            format!("Status codes: {}", join(self.statuses.iter().map(|(k, v)| format!("{}: {}", k, v)).collect())),
+            format!("Content types: {}", join(self.content_types.iter().map(|(k, v)| format!("{}: {}", k, v)).collect())),
+            format!("Excluded: {}", join(self.exclusions.iter().map(|(k, v)| format!("{}: {}", k, v)).collect())),
+            format!("Depths: {}", join(self.depths.iter().map(|(k, v)| format!("{}: {}", k, v)).collect())),
+        ];
+        let slowest = self.slowest(5);
+        if !slowest.is_empty() {
+            lines.push(String::from("Slowest pages:"));
+            for (url, duration) in slowest {
+                lines.push(format!("  {} ms  {}", millis(duration), url));
+            }
+        }
+        lines
+    }
+
+    pub fn write_json(&self, path: &str, urls_added: usize) -> io::Result<()> {
+        let report = json!({
+            "started": self.started.to_rfc3339_opts(SecondsFormat::Secs, true),
+            "duration_ms": millis(self.duration),
+            "pages_fetched": self.fetched,
+            "pages_failed": self.failed,
+            "urls_added": urls_added,
+            "total_bytes": self.total_bytes,
+            "response_time_ms": {
+                "average": millis(self.average_time()),
+                "p95": millis(self.percentile_time(95.0)),
+                "max": millis(self.percentile_time(100.0)),
+            },
+            "status_codes": self.statuses.iter().map(|(k, v)| (k.to_string(), json!(v))).collect::<serde_json::Map<_, _>>(),
+            "content_types": self.content_types,
+            "exclusions": self.exclusions,
+            "depths": self.depths.iter().map(|(k, v)| (k.to_string(), json!(v))).collect::<serde_json::Map<_, _>>(),
+            "slowest": self.slowest(10).iter().map(|(u, d)| json!({ "url": u.as_str(), "duration_ms": millis(*d) })).collect::<Vec<_>>(),
+        });
+        output::write_atomic(path, |out| {
+            serde_json::to_writer_pretty(&mut *out, &report)?;
+            writeln!(out)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(stats: &mut CrawlStats, path: &str, millis: u64, decision: &'static str) {
+        let url = Url::parse("https://foo.bar/").unwrap().join(path).unwrap();
+        stats.record(&FetchEvent {
+            url: &url,
+            status: Some(if decision == "error" { 500 } else { 200 }),
+            content_type: Some("text/HTML; charset=utf-8"),
+            bytes: Some(100),
+            duration: Duration::from_millis(millis),
+            depth: path.matches('/').count() - 1,
+            referrer: None,
+            source: None,
+            decision,
+        });
+    }
+
+    #[test]
+    fn counts_by_decision() {
+        let mut stats = CrawlStats::new();
+        record(&mut stats, "/", 30, "added");
+        record(&mut stats, "/a", 10, "noindex");
+        record(&mut stats, "/b", 50, "error");
+        record(&mut stats, "/c.zip", 0, "skipped");
+        stats.exclude("noindex", "https://foo.bar/a");
+        assert_eq!((stats.fetched, stats.failed, stats.total_bytes), (3, 1, 300));
+        assert_eq!(stats.statuses.get(&200), Some(&2));
+        assert_eq!(stats.content_types.get("text/html"), Some(&3));
+        assert_eq!(stats.exclusions.get("noindex"), Some(&1));
+        assert_eq!(stats.exclusions.get("content type"), Some(&1));
+        assert_eq!(stats.exclusions.get("error"), Some(&1));
+        assert_eq!(stats.depths.get(&0), Some(&3));
+    }
+
+    #[test]
+    fn response_times() {
+        let mut stats = CrawlStats::new();
+        assert_eq!(stats.percentile_time(95.0), Duration::from_millis(0));
+        for (path, millis) in [("/a", 40), ("/b", 10), ("/c", 20), ("/d", 30)].iter() {
+            record(&mut stats, path, *millis, "added");
+        }
+        assert_eq!(stats.average_time(), Duration::from_millis(25));
+        assert_eq!(stats.percentile_time(50.0), Duration::from_millis(20));
+        assert_eq!(stats.percentile_time(95.0), Duration::from_millis(40));
+        assert_eq!(stats.percentile_time(0.0), Duration::from_millis(10));
+        let slowest: Vec<&str> = stats.slowest(2).iter().map(|(u, _)| u.path()).collect();
+        assert_eq!(slowest, vec!["/a", "/d"]);
+        let summary = stats.summary(3);
+        assert_eq!(summary[1], "Pages fetched: 4 (0 failed), urls added: 3");
+        assert_eq!(summary[3], "Response time: average 25 ms, p95 40 ms");
+        assert_eq!(summary[6], "Excluded: -");
+    }
+}
@@ -14,6 +14,14 @@ impl TermWriter {
         }
     }
 
+    /// Writes to stderr, for commands whose stdout is their report.
+    pub fn stderr(active: bool) -> TermWriter {
+        TermWriter {
+            term: Term::stderr(),
+            active,
+        }
+    }
+
     pub fn switch_mode(&mut self) {
         self.active = !self.active;
     }
@@ -22,10 +30,10 @@ impl TermWriter {
         if !self.active {
             return;
         }
-        self.term.clear_last_lines(2);
-        self.term
+        let _ = self.term.clear_last_lines(2);
+        let _ = self.term
             .write_line(&format!("Size of queue on this iteration: {}", links));
-        self.term
+        let _ = self.term
             .write_line(&format!("Total links found: {}", total));
     }
 
@@ -33,9 +41,9 @@ impl TermWriter {
         if !self.active {
             return;
         }
-        self.term
+        let _ = self.term
             .write_line(&format!("Size of queue on this iteration: {}", links));
-        self.term
+        let _ = self.term
             .write_line(&format!("Total links found: {}", total));
     }
 
@@ -43,6 +51,6 @@ impl TermWriter {
         if !self.active {
             return;
         }
-        self.term.write_line(&st);
+        let _ = self.term.write_line(&st);
     }
 }
@@ -0,0 +1,84 @@
+use crate::options::Options;
+use reqwest::blocking::ClientBuilder;
+use reqwest::{Certificate, Identity};
+use std::fs;
+
+fn read(kind: &str, path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("Unable to read {} '{}': {}", kind, path, e))
+}
+
+/// Loads every certificate of a PEM bundle, or a single DER certificate.
+fn load_certificates(path: &str) -> Result<Vec<Certificate>, String> {
+    let data = read("CA file", path)?;
+    let certs = if data.starts_with(b"-----") || data.windows(11).any(|w| w == b"-----BEGIN ") {
+        Certificate::from_pem_bundle(&data)
+    } else {
+        Certificate::from_der(&data).map(|cert| vec![cert])
+    };
+    match certs {
+        Ok(certs) if !certs.is_empty() => Ok(certs),
+        Ok(_) => Err(format!("CA file '{}' contains no certificates.", path)),
+        Err(e) => Err(format!("Invalid CA file '{}': {}", path, e)),
+    }
+}
+
+/// Client certificate for mutual TLS: a PKCS#12 archive, or a PEM certificate with a PKCS#8 key.
+fn load_identity(options: &Options, cert: &str) -> Result<Identity, String> {
+    let data = read("client certificate", cert)?;
+    let identity = match &options.client_key {
+        Some(key) => Identity::from_pkcs8_pem(&data, &read("client key", key)?),
+        None => Identity::from_pkcs12_der(&data, options.client_cert_password.as_deref().unwrap_or("")),
+    };
+    identity.map_err(|e| format!("Invalid client certificate '{}': {}", cert, e))
+}
+
+/// Applies the TLS options of options.cfg to the client.
+pub fn configure(builder: ClientBuilder, options: &Options) -> Result<ClientBuilder, String> {
+    let mut builder = builder;
+    for path in options.ca_files.iter() {
+        for cert in load_certificates(path)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    if options.ca_only {
+        if options.ca_files.is_empty() {
+            return Err(String::from("Option 'ca_only' requires at least one 'ca_file'."));
+        }
+        builder = builder.tls_built_in_root_certs(false);
+    }
+    if let Some(cert) = &options.client_cert {
+        builder = builder.identity(load_identity(options, cert)?);
+    }
+    if options.insecure {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::blocking::Client;
+    use std::env;
+
+    fn error(settings: &[(&str, &str)]) -> String {
+        let mut options = Options::default();
+        for (key, value) in settings.iter() {
+            options.set(key, value).unwrap();
+        }
+        configure(Client::builder(), &options).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn unusable_certificates() {
+        let path = env::temp_dir().join(format!("xml_sitemapper_ca_{}.pem", std::process::id()));
+        let path = path.to_str().unwrap();
+        fs::write(path, "-----BEGIN NOTHING-----\n-----END NOTHING-----\n").unwrap();
+        assert_eq!(error(&[("ca_file", path)]), format!("CA file '{}' contains no certificates.", path));
+        fs::remove_file(path).unwrap();
+        assert!(error(&[("ca_file", path)]).starts_with(&format!("Unable to read CA file '{}'", path)));
+        assert!(error(&[("client_cert", path)]).starts_with(&format!("Unable to read client certificate '{}'", path)));
+        assert_eq!(error(&[("ca_only", "true")]), "Option 'ca_only' requires at least one 'ca_file'.");
+        assert_eq!(error(&[("insecure", "true")]), "");
+    }
+}
@@ -0,0 +1,209 @@
+use crate::http_client;
+use crate::options::Options;
+use flate2::read::GzDecoder;
+use reqwest::blocking::Client;
+use reqwest::Url;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use xml::reader::{EventReader, XmlEvent};
+
+#[derive(Clone, Default, PartialEq)]
+pub struct SitemapEntry {
+    pub loc: String,
+    pub lastmod: Option<String>,
+    pub priority: Option<String>,
+}
+
+pub struct Sitemap {
+    /// True for a <sitemapindex>, whose locations point to other sitemaps.
+    pub is_index: bool,
+    pub locs: Vec<String>,
+    /// Every <url> or <sitemap> element with its optional fields.
+    pub entries: Vec<SitemapEntry>,
+}
+
+/// Collects the text of every <loc> element of a sitemap or sitemap index.
+pub fn parse_sitemap(content: &str) -> io::Result<Sitemap> {
+    let mut urls = Vec::new();
+    let mut entries = Vec::new();
+    let mut entry = SitemapEntry::default();
+    let mut is_index = false;
+    let mut field = String::new();
+    for event in EventReader::from_str(content) {
+        match event {
+            Ok(XmlEvent::StartElement { name, .. }) => {
+                if name.local_name == "sitemapindex" {
+                    is_index = true;
+                }
+                field = name.local_name;
+            }
+            Ok(XmlEvent::Characters(text)) => {
+                let text = String::from(text.trim());
+                match field.as_str() {
+                    "loc" => {
+                        urls.push(text.clone());
+                        entry.loc = text;
+                    }
+                    "lastmod" => entry.lastmod = Some(text),
+                    "priority" => entry.priority = Some(text),
+                    _ => {}
+                }
+            }
+            Ok(XmlEvent::EndElement { name }) => {
+                if matches!(name.local_name.as_str(), "url" | "sitemap") && !entry.loc.is_empty() {
+                    entries.push(std::mem::take(&mut entry));
+                }
+                field.clear();
+            }
+            Ok(_) => {}
+            Err(e) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string()));
+            }
+        }
+    }
+    Ok(Sitemap { is_index, locs: urls, entries })
+}
+
+/// Turns raw file or response bytes into text, unpacking them first if they are gzipped.
+pub fn decode(bytes: &[u8]) -> io::Result<String> {
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut content = String::new();
+        GzDecoder::new(bytes).read_to_string(&mut content)?;
+        Ok(content)
+    } else {
+        String::from_utf8(bytes.to_vec()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Values of the `Sitemap:` lines of a robots.txt file.
+pub fn robots_sitemaps(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            if key.trim().eq_ignore_ascii_case("sitemap") {
+                Some(String::from(value.trim()))
+            } else {
+                None
+            }
+        })
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// Takes the first column of every row which looks like an absolute URL, so header rows are skipped.
+fn parse_csv(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| line.split(',').next())
+        .map(|cell| cell.trim().trim_matches('"').trim())
+        .filter(|cell| cell.starts_with("http://") || cell.starts_with("https://"))
+        .map(String::from)
+        .collect()
+}
+
+fn parse_plain(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+/// Parses a list of URLs given as a sitemap, a CSV file (URL in the first column) or plain text
+/// with one URL per line.
+pub fn parse_url_list(name: &str, content: &str) -> io::Result<Vec<String>> {
+    if content.trim_start().starts_with('<') {
+        Ok(parse_sitemap(content)?.locs)
+    } else if name.to_lowercase().ends_with(".csv") {
+        Ok(parse_csv(content))
+    } else {
+        Ok(parse_plain(content))
+    }
+}
+
+pub fn read_url_list(path: &str) -> io::Result<Vec<String>> {
+    let content = decode(&fs::read(path)?)?;
+    parse_url_list(path.trim_end_matches(".gz"), &content)
+}
+
+/// Reads a sitemap from a file or URL. With `local_dir` URLs are looked up there by their file
+/// name first, since a sitemap index written by a crawl points to sitemaps written next to it.
+/// Downloads only carry the credentials of `options` when they are on the origin of `root`,
+/// the sitemap the reading started from.
+pub fn read_location(location: &str, root: &str, local_dir: Option<&Path>, client: &Client, options: &Options) -> Result<Vec<u8>, String> {
+    if location.starts_with("http://") || location.starts_with("https://") {
+        let name = location.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+        if let Some(local) = local_dir.map(|dir| dir.join(name)) {
+            if !name.is_empty() && local.is_file() {
+                return fs::read(&local).map_err(|e| format!("Unable to read '{}': {}", local.display(), e));
+            }
+        }
+        let mut request = client.get(location);
+        let origin = |location: &str| Url::parse(location).ok().map(|u| u.origin());
+        if origin(location).is_some_and(|o| Some(o) == origin(root)) {
+            request = http_client::authorize(request, options);
+        }
+        let mut bytes = Vec::new();
+        request
+            .send()
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| format!("Unable to download '{}': {}", location, e))?
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("Unable to download '{}': {}", location, e))?;
+        Ok(bytes)
+    } else {
+        fs::read(location).map_err(|e| format!("Unable to read '{}': {}", location, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_list_formats() {
+        let csv = "URL,Status\n\"https://foo.bar/a\",200\nhttps://foo.bar/b , 404\nnot a url,1\n";
+        assert_eq!(parse_url_list("export.CSV", csv).unwrap(), vec!["https://foo.bar/a", "https://foo.bar/b"]);
+        let plain = "# known pages\nhttps://foo.bar/a\n\n  https://foo.bar/b  \n";
+        assert_eq!(parse_url_list("urls.txt", plain).unwrap(), vec!["https://foo.bar/a", "https://foo.bar/b"]);
+        let xml = "<?xml version=\"1.0\"?><urlset><url><loc> https://foo.bar/a </loc></url></urlset>";
+        assert_eq!(parse_url_list("urls.csv", xml).unwrap(), vec!["https://foo.bar/a"]);
+        assert!(parse_url_list("urls.xml", "<urlset><url>").is_err());
+    }
+
+    #[test]
+    fn sitemap_entries_and_index() {
+        let xml = "<urlset><url><loc>https://foo.bar/a</loc><lastmod>2024-01-01</lastmod><priority>0.5</priority></url>\
+                   <url><loc>https://foo.bar/b</loc></url></urlset>";
+        let sitemap = parse_sitemap(xml).unwrap();
+        assert!(!sitemap.is_index);
+        assert_eq!(sitemap.locs, vec!["https://foo.bar/a", "https://foo.bar/b"]);
+        assert_eq!(sitemap.entries[0].lastmod.as_deref(), Some("2024-01-01"));
+        assert_eq!(sitemap.entries[0].priority.as_deref(), Some("0.5"));
+        assert!(sitemap.entries[1].lastmod.is_none());
+        let index = parse_sitemap("<sitemapindex><sitemap><loc>https://foo.bar/s1.xml</loc></sitemap></sitemapindex>").unwrap();
+        assert!(index.is_index);
+        assert_eq!(index.locs, vec!["https://foo.bar/s1.xml"]);
+    }
+
+    #[test]
+    fn decode_gzip() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"<urlset/>").unwrap();
+        assert_eq!(decode(&encoder.finish().unwrap()).unwrap(), "<urlset/>");
+        assert_eq!(decode(b"plain").unwrap(), "plain");
+        assert!(decode(&[0xff, 0xfe]).is_err());
+    }
+
+    #[test]
+    fn sitemaps_from_robots() {
+        let robots = "User-agent: *\nDisallow: /a\nSITEMAP: https://foo.bar/sitemap.xml\nSitemap:\n# Sitemap: https://foo.bar/x.xml\n";
+        assert_eq!(robots_sitemaps(robots), vec!["https://foo.bar/sitemap.xml"]);
+    }
+}
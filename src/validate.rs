@@ -0,0 +1,480 @@
+use crate::options::Options;
+use crate::{http_client, url_list};
+use regex::Regex;
+use reqwest::blocking::Client;
+use reqwest::header::{CONTENT_TYPE, LOCATION};
+use reqwest::Url;
+use select::document::Document;
+use select::predicate::Name;
+use serde_json::json;
+use std::collections::{HashSet, VecDeque};
+use std::path::Path;
+use xml::common::Position;
+use xml::reader::{EventReader, XmlEvent};
+
+const NAMESPACE: &str = "http://www.sitemaps.org/schemas/sitemap/0.9";
+const MAX_URLS: usize = 50_000;
+const MAX_BYTES: usize = 50 * 1024 * 1024;
+const MAX_LOC_LENGTH: usize = 2048;
+
+pub struct Issue {
+    pub error: bool,
+    pub sitemap: String,
+    pub line: Option<u64>,
+    pub url: Option<String>,
+    pub message: String,
+}
+
+/// Problems found in a sitemap and the sitemaps of an index.
+#[derive(Default)]
+pub struct Report {
+    pub issues: Vec<Issue>,
+    pub sitemaps: usize,
+    /// Every `<loc>` of the checked `<urlset>` files, for the live checks.
+    pub urls: Vec<String>,
+    host: Option<String>,
+}
+
+#[derive(Default)]
+struct Entry {
+    line: u64,
+    loc: Option<String>,
+    lastmod: Option<String>,
+    changefreq: Option<String>,
+    priority: Option<String>,
+}
+
+impl Report {
+    fn add(&mut self, error: bool, sitemap: &str, line: Option<u64>, url: Option<&str>, message: String) {
+        self.issues.push(Issue {
+            error,
+            sitemap: String::from(sitemap),
+            line,
+            url: url.map(String::from),
+            message,
+        });
+    }
+
+    pub fn errors(&self) -> usize {
+        self.issues.iter().filter(|i| i.error).count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.issues.len() - self.errors()
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .issues
+            .iter()
+            .map(|i| {
+                let mut line = format!("{}: {}", if i.error { "error" } else { "warning" }, i.sitemap);
+                if let Some(row) = i.line {
+                    line += &format!(":{}", row);
+                }
+                if let Some(url) = &i.url {
+                    line += &format!(": {}", url);
+                }
+                line + ": " + &i.message
+            })
+            .collect();
+        lines.push(format!(
+            "Checked {} sitemaps with {} urls: {} errors, {} warnings.",
+            self.sitemaps,
+            self.urls.len(),
+            self.errors(),
+            self.warnings()
+        ));
+        lines
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "sitemaps": self.sitemaps,
+            "urls": self.urls.len(),
+            "errors": self.errors(),
+            "warnings": self.warnings(),
+            "issues": self.issues.iter().map(|i| json!({
+                "level": if i.error { "error" } else { "warning" },
+                "sitemap": i.sitemap,
+                "line": i.line,
+                "url": i.url,
+                "message": i.message,
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Unescaped `&` is the most common escaping mistake; xml-rs only reports the first one.
+    /// Comments and CDATA sections may hold a bare `&`, so they are skipped.
+    fn check_escaping(&mut self, sitemap: &str, text: &str) {
+        let entity = Regex::new(r"^&(amp|lt|gt|quot|apos|#[0-9]+|#x[0-9a-fA-F]+);").unwrap();
+        let mut closing: Option<&str> = None;
+        for (row, line) in text.lines().enumerate() {
+            let mut rest = line;
+            loop {
+                if let Some(end) = closing {
+                    match rest.find(end) {
+                        Some(pos) => {
+                            rest = &rest[pos + end.len()..];
+                            closing = None;
+                        }
+                        None => {
+                            break;
+                        }
+                    }
+                }
+                let amp = rest.find('&');
+                let section = [("<!--", "-->"), ("<![CDATA[", "]]>")]
+                    .iter()
+                    .filter_map(|(open, close)| rest.find(open).map(|pos| (pos, open.len(), *close)))
+                    .min();
+                match (amp, section) {
+                    (Some(pos), section) if section.is_none_or(|(start, _, _)| pos < start) => {
+                        if !entity.is_match(&rest[pos..]) {
+                            self.add(true, sitemap, Some(row as u64 + 1), None, String::from("'&' must be escaped as &amp;"));
+                        }
+                        rest = &rest[pos + 1..];
+                    }
+                    (_, Some((start, len, close))) => {
+                        rest = &rest[start + len..];
+                        closing = Some(close);
+                    }
+                    _ => {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_entry(&mut self, sitemap: &str, is_index: bool, entry: Entry, seen: &mut HashSet<String>, w3c: &Regex) -> Option<String> {
+        let line = Some(entry.line);
+        let loc = match entry.loc {
+            Some(loc) => loc,
+            None => {
+                self.add(true, sitemap, line, None, String::from("entry has no <loc>"));
+                return None;
+            }
+        };
+        let url = Some(loc.as_str());
+        if loc.len() > MAX_LOC_LENGTH {
+            self.add(true, sitemap, line, url, format!("<loc> is longer than {} characters", MAX_LOC_LENGTH));
+        }
+        if loc.chars().any(|c| c.is_whitespace() || !c.is_ascii()) {
+            self.add(true, sitemap, line, url, String::from("<loc> contains spaces or non-ASCII characters, which must be URL-escaped"));
+        }
+        match Url::parse(&loc) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {
+                let host = String::from(parsed.host_str().unwrap_or(""));
+                match &self.host {
+                    Some(expected) if *expected != host => {
+                        self.add(true, sitemap, line, url, format!("<loc> is on host '{}', expected '{}'", host, expected));
+                    }
+                    Some(_) => {}
+                    None => self.host = Some(host),
+                }
+            }
+            _ => self.add(true, sitemap, line, url, String::from("<loc> is not an absolute http(s) URL")),
+        }
+        if !seen.insert(loc.clone()) {
+            self.add(false, sitemap, line, url, String::from("duplicate <loc>"));
+        }
+        if let Some(lastmod) = &entry.lastmod {
+            if !w3c.is_match(lastmod) {
+                self.add(true, sitemap, line, url, format!("<lastmod> '{}' is not a W3C datetime", lastmod));
+            }
+        }
+        if let Some(changefreq) = &entry.changefreq {
+            if is_index {
+                self.add(false, sitemap, line, url, String::from("<changefreq> is not allowed in a sitemap index"));
+            } else if !matches!(changefreq.as_str(), "always" | "hourly" | "daily" | "weekly" | "monthly" | "yearly" | "never") {
+                self.add(true, sitemap, line, url, format!("<changefreq> '{}' is not a valid value", changefreq));
+            }
+        }
+        if let Some(priority) = &entry.priority {
+            if is_index {
+                self.add(false, sitemap, line, url, String::from("<priority> is not allowed in a sitemap index"));
+            } else if !priority.parse::<f64>().is_ok_and(|p| (0.0..=1.0).contains(&p)) {
+                self.add(true, sitemap, line, url, format!("<priority> '{}' is not a number between 0.0 and 1.0", priority));
+            }
+        }
+        Some(loc)
+    }
+
+    /// Checks one file, returning the child sitemaps if it is an index.
+    fn check_sitemap(&mut self, sitemap: &str, text: &str) -> Vec<String> {
+        self.sitemaps += 1;
+        if text.len() > MAX_BYTES {
+            self.add(true, sitemap, None, None, format!("sitemap is larger than {} bytes uncompressed", MAX_BYTES));
+        }
+        self.check_escaping(sitemap, text);
+        let mut reader = EventReader::from_str(text);
+        let mut is_index = false;
+        let mut root = true;
+        let mut entry: Option<Entry> = None;
+        let mut field = String::new();
+        let mut count = 0;
+        let mut seen = HashSet::new();
+        let mut children = Vec::new();
+        let w3c = Regex::new(r"^\d{4}(-\d{2}(-\d{2}(T\d{2}:\d{2}(:\d{2}(\.\d+)?)?(Z|[+-]\d{2}:\d{2}))?)?)?$").unwrap();
+        loop {
+            let event = reader.next();
+            let line = reader.position().row + 1;
+            match event {
+                Ok(XmlEvent::StartElement { name, .. }) => {
+                    if root {
+                        root = false;
+                        is_index = name.local_name == "sitemapindex";
+                        if !is_index && name.local_name != "urlset" {
+                            self.add(true, sitemap, Some(line), None, format!("root element is <{}>, expected <urlset> or <sitemapindex>", name.local_name));
+                        }
+                        if name.namespace.as_deref() != Some(NAMESPACE) {
+                            self.add(true, sitemap, Some(line), None, format!("root element must use the namespace {}", NAMESPACE));
+                        }
+                    } else if matches!(name.local_name.as_str(), "url" | "sitemap") {
+                        entry = Some(Entry { line, ..Entry::default() });
+                    }
+                    field = name.local_name;
+                }
+                Ok(XmlEvent::Characters(value)) | Ok(XmlEvent::CData(value)) => {
+                    if let Some(entry) = entry.as_mut() {
+                        let value = Some(String::from(value.trim()));
+                        match field.as_str() {
+                            "loc" => entry.loc = value,
+                            "lastmod" => entry.lastmod = value,
+                            "changefreq" => entry.changefreq = value,
+                            "priority" => entry.priority = value,
+                            _ => {}
+                        }
+                    }
+                }
+                Ok(XmlEvent::EndElement { name }) => {
+                    if matches!(name.local_name.as_str(), "url" | "sitemap") {
+                        if let Some(done) = entry.take() {
+                            count += 1;
+                            if let Some(loc) = self.check_entry(sitemap, is_index, done, &mut seen, &w3c) {
+                                if is_index {
+                                    children.push(loc);
+                                } else {
+                                    self.urls.push(loc);
+                                }
+                            }
+                        }
+                    }
+                    field.clear();
+                }
+                Ok(XmlEvent::EndDocument) => break,
+                Ok(_) => {}
+                Err(e) => {
+                    self.add(true, sitemap, Some(e.position().row + 1), None, format!("XML error: {}", e.msg()));
+                    break;
+                }
+            }
+        }
+        if count > MAX_URLS {
+            self.add(true, sitemap, None, None, format!("sitemap has {} entries, at most {} are allowed", count, MAX_URLS));
+        }
+        if count == 0 && !root {
+            self.add(false, sitemap, None, None, String::from("sitemap has no entries"));
+        }
+        children
+    }
+
+    /// Fetches every URL without following redirects and flags the ones that should not be listed.
+    pub fn check_live(&mut self, client: &Client, options: &Options) {
+        for loc in self.urls.clone() {
+            let url = Some(loc.as_str());
+            let mut request = client.get(&loc);
+            // Credentials only go to the host of the sitemap.
+            if Url::parse(&loc).is_ok_and(|u| u.host_str().map(String::from) == self.host) {
+                request = http_client::authorize(request, options);
+            }
+            let res = match request.send() {
+                Ok(res) => res,
+                Err(e) => {
+                    self.add(true, "live", None, url, format!("request failed: {}", e));
+                    continue;
+                }
+            };
+            let status = res.status();
+            if status.is_redirection() {
+                let target = res.headers().get(LOCATION).and_then(|h| h.to_str().ok()).unwrap_or("?");
+                self.add(true, "live", None, url, format!("redirects ({}) to {}", status.as_u16(), target));
+                continue;
+            }
+            if status.as_u16() != 200 {
+                self.add(true, "live", None, url, format!("returns status {}", status.as_u16()));
+                continue;
+            }
+            let robots_header = res
+                .headers()
+                .get_all("X-Robots-Tag")
+                .iter()
+                .filter_map(|h| h.to_str().ok())
+                .any(|h| h.to_lowercase().contains("noindex"));
+            if robots_header {
+                self.add(true, "live", None, url, String::from("is noindex by X-Robots-Tag"));
+            }
+            let is_html = res
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|h| h.to_str().ok())
+                .is_some_and(|h| h.starts_with("text/html"));
+            if !is_html {
+                continue;
+            }
+            let html = match res.text() {
+                Ok(html) => Document::from(html.as_str()),
+                Err(_) => {
+                    continue;
+                }
+            };
+            let noindex = html.find(Name("meta")).any(|m| {
+                m.attr("name").is_some_and(|n| n.eq_ignore_ascii_case("robots"))
+                    && m.attr("content").is_some_and(|c| c.to_lowercase().contains("noindex"))
+            });
+            if noindex {
+                self.add(true, "live", None, url, String::from("is noindex by <meta name=robots>"));
+            }
+            let canonical = html
+                .find(Name("link"))
+                .find(|l| l.attr("rel").is_some_and(|r| r.eq_ignore_ascii_case("canonical")))
+                .and_then(|l| l.attr("href"))
+                .and_then(|href| Url::parse(&loc).ok()?.join(href).ok());
+            if let Some(canonical) = canonical {
+                if Url::parse(&loc).ok().as_ref() != Some(&canonical) {
+                    self.add(false, "live", None, url, format!("canonical URL is {}", canonical));
+                }
+            }
+        }
+    }
+}
+
+/// Checks a sitemap or a sitemap index with all sitemaps it points to.
+/// With `prefer_local` the sitemaps of an index are read from files next to it when they exist.
+pub fn validate(location: &str, client: &Client, options: &Options, prefer_local: bool) -> Report {
+    let local_dir = Path::new(location).parent().filter(|_| prefer_local);
+    let mut report = Report::default();
+    if let Ok(url) = Url::parse(location) {
+        report.host = url.host_str().map(String::from);
+    }
+    let mut queue = VecDeque::new();
+    let mut seen = HashSet::new();
+    queue.push_back(String::from(location));
+    while let Some(sitemap) = queue.pop_front() {
+        if !seen.insert(sitemap.clone()) {
+            continue;
+        }
+        let text = url_list::read_location(&sitemap, location, local_dir, client, options)
+            .and_then(|bytes| url_list::decode(&bytes).map_err(|e| format!("Unable to decode '{}': {}", sitemap, e)));
+        match text {
+            Ok(text) => queue.extend(report.check_sitemap(&sitemap, &text)),
+            Err(e) => report.add(true, &sitemap, None, None, e),
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urlset(entries: &str) -> String {
+        format!("<?xml version=\"1.0\"?>\n<urlset xmlns=\"{}\">\n{}\n</urlset>\n", NAMESPACE, entries)
+    }
+
+    fn check(text: &str) -> Report {
+        let mut report = Report::default();
+        report.check_sitemap("sitemap.xml", text);
+        report
+    }
+
+    fn messages(report: &Report) -> Vec<String> {
+        report.issues.iter().map(|i| format!("{}:{}", i.line.unwrap_or(0), i.message)).collect()
+    }
+
+    #[test]
+    fn valid_sitemap() {
+        let report = check(&urlset(
+            "<url><loc>https://foo.bar/?a=1&amp;b=2</loc><lastmod>2024-01-02T03:04:05+01:00</lastmod><changefreq>daily</changefreq><priority>0.8</priority></url>",
+        ));
+        assert_eq!(messages(&report), Vec::<String>::new());
+        assert_eq!(report.urls, vec![String::from("https://foo.bar/?a=1&b=2")]);
+    }
+
+    #[test]
+    fn escaping_skips_comments_and_cdata() {
+        let mut report = Report::default();
+        report.check_escaping("s.xml", "<!-- a & b\n still & a comment -->\n<loc><![CDATA[?a=1&b=2]]></loc>\n<loc>?a=1&amp;b=2&#38;c&#x26;d</loc>\n<loc>?a=1&b=2</loc> <!-- & -->");
+        assert_eq!(messages(&report), vec![String::from("5:'&' must be escaped as &amp;")]);
+    }
+
+    #[test]
+    fn escaping_reports_every_bare_ampersand() {
+        let mut report = Report::default();
+        report.check_escaping("s.xml", "<loc>?a&b&c</loc>\n<loc>&lt;&unknown;</loc>");
+        assert_eq!(report.errors(), 3);
+    }
+
+    #[test]
+    fn w3c_datetimes() {
+        for valid in ["2024", "2024-01", "2024-01-02", "2024-01-02T03:04Z", "2024-01-02T03:04:05.5-07:00"] {
+            let report = check(&urlset(&format!("<url><loc>https://foo.bar/</loc><lastmod>{}</lastmod></url>", valid)));
+            assert_eq!(report.errors(), 0, "{}", valid);
+        }
+        for invalid in ["2024-1-2", "2024-01-02T03:04", "2024-01-02 03:04:05Z", "02.01.2024", "2024-01-02T03:04:05+0100"] {
+            let report = check(&urlset(&format!("<url><loc>https://foo.bar/</loc><lastmod>{}</lastmod></url>", invalid)));
+            assert_eq!(report.errors(), 1, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn locs_must_share_one_host() {
+        let report = check(&urlset("<url><loc>https://foo.bar/a</loc></url>\n<url><loc>https://cdn.foo.bar/b</loc></url>\n<url><loc>/relative</loc></url>"));
+        assert_eq!(
+            messages(&report),
+            vec![
+                String::from("4:<loc> is on host 'cdn.foo.bar', expected 'foo.bar'"),
+                String::from("5:<loc> is not an absolute http(s) URL"),
+            ]
+        );
+    }
+
+    #[test]
+    fn entry_values() {
+        let report = check(&urlset(
+            "<url><loc>https://foo.bar/a</loc><changefreq>sometimes</changefreq><priority>1.3</priority></url>\n<url><loc>https://foo.bar/a</loc></url>",
+        ));
+        assert_eq!(report.errors(), 2);
+        assert_eq!(report.warnings(), 1);
+        assert!(messages(&report).contains(&String::from("4:duplicate <loc>")));
+    }
+
+    #[test]
+    fn index_rules() {
+        let text = format!(
+            "<?xml version=\"1.0\"?>\n<sitemapindex xmlns=\"{}\">\n<sitemap><loc>https://foo.bar/a.xml</loc><priority>0.5</priority></sitemap>\n</sitemapindex>\n",
+            NAMESPACE
+        );
+        let mut report = Report::default();
+        let children = report.check_sitemap("index.xml", &text);
+        assert_eq!(children, vec![String::from("https://foo.bar/a.xml")]);
+        assert!(report.urls.is_empty());
+        assert_eq!(messages(&report), vec![String::from("3:<priority> is not allowed in a sitemap index")]);
+        assert_eq!(report.errors(), 0);
+    }
+
+    #[test]
+    fn root_element_and_namespace() {
+        let report = check("<?xml version=\"1.0\"?>\n<urls><url><loc>https://foo.bar/</loc></url></urls>");
+        assert_eq!(
+            messages(&report),
+            vec![
+                String::from("2:root element is <urls>, expected <urlset> or <sitemapindex>"),
+                String::from("2:root element must use the namespace http://www.sitemaps.org/schemas/sitemap/0.9"),
+            ]
+        );
+        let empty = check(&urlset(""));
+        assert_eq!(messages(&empty), vec![String::from("0:sitemap has no entries")]);
+    }
+}
@@ -1,47 +1,138 @@
-use std::fs::File;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
 use xml::common::XmlVersion;
-use xml::writer::{EmitterConfig, XmlEvent};
+use xml::writer::{EmitterConfig, Error, XmlEvent};
 
+fn to_io(e: Error) -> io::Error {
+    match e {
+        Error::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+    }
+}
+
+/// Writes an XML file next to its destination and moves it into place only in `finish`,
+/// so a failed write never replaces the previous file with a truncated one.
 pub struct XmlWriter {
-    wr_buf: xml::EventWriter<File>,
+    wr_buf: Option<xml::EventWriter<BufWriter<File>>>,
+    path: String,
+    tmp_path: String,
 }
 
 impl XmlWriter {
-    pub fn new(file: File) -> XmlWriter {
+    pub fn create(path: &str) -> io::Result<XmlWriter> {
+        let tmp_path = format!("{}.tmp", path);
+        let file = File::create(&tmp_path)?;
         let mut xml_writer = XmlWriter {
-            wr_buf: EmitterConfig::new()
-                .perform_indent(true)
-                .create_writer(file),
+            wr_buf: Some(
+                EmitterConfig::new()
+                    .perform_indent(true)
+                    .create_writer(BufWriter::new(file)),
+            ),
+            path: String::from(path),
+            tmp_path,
         };
-        xml_writer.wr_buf.write(XmlEvent::StartDocument {
+        xml_writer.write(XmlEvent::StartDocument {
             version: XmlVersion::Version10,
             standalone: None,
             encoding: Some("UTF-8"),
-        });
-        xml_writer
+        })?;
+        Ok(xml_writer)
+    }
+
+    fn write<'a, E: Into<XmlEvent<'a>>>(&mut self, event: E) -> io::Result<()> {
+        match self.wr_buf.as_mut() {
+            Some(wr_buf) => wr_buf.write(event).map_err(to_io),
+            None => Err(io::Error::other("writer is already finished")),
+        }
     }
 
-    pub fn write_element(&mut self, key: String, val: String) {
-        self.wr_buf.write(XmlEvent::start_element(key.as_str()));
-        self.wr_buf.write(XmlEvent::characters(val.as_str()));
-        self.wr_buf.write(XmlEvent::end_element());
+    pub fn write_element(&mut self, key: String, val: String) -> io::Result<()> {
+        self.write(XmlEvent::start_element(key.as_str()))?;
+        self.write(XmlEvent::characters(val.as_str()))?;
+        self.write(XmlEvent::end_element())
     }
 
-    pub fn open_element(&mut self, key: String) {
-        self.wr_buf.write(XmlEvent::start_element(key.as_str()));
+    pub fn open_element(&mut self, key: String) -> io::Result<()> {
+        self.write(XmlEvent::start_element(key.as_str()))
     }
 
-    pub fn open_element_attr(&mut self, key: String, attr_key: String, attr_val: String) {
-        self.wr_buf.write(
-            XmlEvent::start_element(key.as_str()).attr(attr_key.as_str(), attr_val.as_str()),
-        );
+    pub fn open_element_attr(&mut self, key: String, attr_key: String, attr_val: String) -> io::Result<()> {
+        self.write(XmlEvent::start_element(key.as_str()).attr(attr_key.as_str(), attr_val.as_str()))
+    }
+
+    pub fn close_element(&mut self) -> io::Result<()> {
+        self.write(XmlEvent::end_element())
+    }
+
+    pub fn comment(&mut self, st: String) -> io::Result<()> {
+        self.write(XmlEvent::comment(&st))
+    }
+
+    /// Flushes the file to disk and renames it to its final name.
+    pub fn finish(mut self) -> io::Result<()> {
+        let wr_buf = match self.wr_buf.take() {
+            Some(wr_buf) => wr_buf,
+            None => return Err(io::Error::other("writer is already finished")),
+        };
+        let result = wr_buf
+            .into_inner()
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .and_then(|file| file.sync_all())
+            .and_then(|_| fs::rename(&self.tmp_path, &self.path));
+        if result.is_err() {
+            let _ = fs::remove_file(&self.tmp_path);
+        }
+        result
+    }
+}
+
+impl Drop for XmlWriter {
+    fn drop(&mut self) {
+        // Not finished: an error happened on the way, so drop the partial file.
+        if self.wr_buf.take().is_some() {
+            let _ = fs::remove_file(&self.tmp_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::Path;
+
+    fn path(name: &str) -> String {
+        let path = env::temp_dir().join(format!("xml_sitemapper_{}_{}.xml", name, std::process::id()));
+        String::from(path.to_str().unwrap())
     }
 
-    pub fn close_element(&mut self) {
-        self.wr_buf.write(XmlEvent::end_element());
+    #[test]
+    fn finish_moves_the_file_into_place() {
+        let path = path("writer_finish");
+        fs::write(&path, "old").unwrap();
+        let mut writer = XmlWriter::create(&path).unwrap();
+        writer.write_element(String::from("loc"), String::from("https://foo.bar/?a=1&b=2")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        writer.finish().unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(content.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+        assert!(content.contains("<loc>https://foo.bar/?a=1&amp;b=2</loc>"));
+        assert!(!Path::new(&format!("{}.tmp", path)).exists());
     }
 
-    pub fn comment(&mut self, st: String) {
-        self.wr_buf.write(XmlEvent::comment(&st));
+    #[test]
+    fn unfinished_writes_keep_the_old_file() {
+        let path = path("writer_drop");
+        fs::write(&path, "old").unwrap();
+        let mut writer = XmlWriter::create(&path).unwrap();
+        writer.open_element(String::from("urlset")).unwrap();
+        assert!(writer.close_element().is_ok());
+        assert!(writer.close_element().is_err());
+        drop(writer);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert!(!Path::new(&format!("{}.tmp", path)).exists());
+        fs::remove_file(&path).unwrap();
     }
 }